@@ -1,49 +1,66 @@
 //! Selection deltas definitions.
-use std::{cmp, vec};
 
-use crate::{utils::UniqueSortedVec, Position, Selection};
+mod accumulator;
+mod lines;
+
+use std::{cmp, slice, vec};
+
+pub use accumulator::DeltasAccumulator;
+
+use crate::{utils::UniqueSortedVec, Position, Selection, SelectionDirection, SelectionId};
 
 /// Info on changed selection.
-#[derive(Debug, PartialEq, Eq)]
-pub enum SelectionDelta<'a> {
+#[derive(Debug)]
+pub enum SelectionDelta<'a, T = ()> {
     /// Selection was created
-    Created(&'a Selection),
+    Created(&'a Selection<T>),
     /// Selection was deleted
-    Deleted(Selection),
+    Deleted(Selection<T>),
     /// Selection was updated
     Updated {
         /// Old selection state
-        old: Selection,
+        old: Selection<T>,
         /// New selection state
-        new: &'a Selection,
+        new: &'a Selection<T>,
     },
 }
 
-impl PartialOrd for SelectionDelta<'_> {
-    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
-        match self
-            .get_from_position()
-            .partial_cmp(other.get_from_position())
-        {
-            Some(std::cmp::Ordering::Equal) => {
-                if matches!(self, SelectionDelta::Deleted(_)) {
-                    Some(std::cmp::Ordering::Less)
-                } else {
-                    Some(std::cmp::Ordering::Greater)
-                }
-            }
-            other => other,
+impl<T> PartialEq for SelectionDelta<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (SelectionDelta::Created(a), SelectionDelta::Created(b)) => a == b,
+            (SelectionDelta::Deleted(a), SelectionDelta::Deleted(b)) => a == b,
+            (
+                SelectionDelta::Updated { old, new },
+                SelectionDelta::Updated {
+                    old: other_old,
+                    new: other_new,
+                },
+            ) => old == other_old && new == other_new,
+            _ => false,
         }
     }
 }
 
-impl Ord for SelectionDelta<'_> {
+impl<T> Eq for SelectionDelta<'_, T> {}
+
+impl<T> PartialOrd for SelectionDelta<'_, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for SelectionDelta<'_, T> {
     fn cmp(&self, other: &Self) -> cmp::Ordering {
-        self.partial_cmp(other).expect("total order defined")
+        cmp_deltas(
+            self.get_from_position(),
+            matches!(self, SelectionDelta::Deleted(_)),
+            other.get_from_position(),
+        )
     }
 }
 
-impl SelectionDelta<'_> {
+impl<T> SelectionDelta<'_, T> {
     /// Shortcut to get `from` coordinate required for comparison
     fn get_from_position(&self) -> &Position {
         match self {
@@ -53,25 +70,214 @@ impl SelectionDelta<'_> {
     }
 }
 
+impl<T: Clone> SelectionDelta<'_, T> {
+    /// Clones borrowed selections to detach the delta from selections storage.
+    pub fn into_owned(self) -> OwnedSelectionDelta<T> {
+        self.into_owned_with(Selection::clone)
+    }
+}
+
+/// Function cloning a selection, it lets deltas be detached where `T: Clone`
+/// bound isn't available, but known to hold.
+pub(crate) type CloneSelection<T> = fn(&Selection<T>) -> Selection<T>;
+
+impl<T> SelectionDelta<'_, T> {
+    /// Detaches the delta from selections storage cloning borrowed selections
+    /// with `clone`.
+    pub(crate) fn into_owned_with(self, clone: CloneSelection<T>) -> OwnedSelectionDelta<T> {
+        match self {
+            SelectionDelta::Created(s) => OwnedSelectionDelta::Created(clone(s)),
+            SelectionDelta::Deleted(s) => OwnedSelectionDelta::Deleted(s),
+            SelectionDelta::Updated { old, new } => OwnedSelectionDelta::Updated {
+                old,
+                new: clone(new),
+            },
+        }
+    }
+}
+
+/// Deltas are ordered by `from` position of a selection (old one for `Updated`)
+/// and `Deleted` goes first on collision.
+fn cmp_deltas(from: &Position, is_deleted: bool, other_from: &Position) -> cmp::Ordering {
+    match from.cmp(other_from) {
+        cmp::Ordering::Equal => {
+            if is_deleted {
+                cmp::Ordering::Less
+            } else {
+                cmp::Ordering::Greater
+            }
+        }
+        other => other,
+    }
+}
+
+/// Info on changed selection that owns selections, so it's not bound to
+/// [SelectionStorage](crate::SelectionStorage) lifetime and could be stored or
+/// sent elsewhere.
+#[derive(Debug, Clone)]
+pub enum OwnedSelectionDelta<T = ()> {
+    /// Selection was created
+    Created(Selection<T>),
+    /// Selection was deleted
+    Deleted(Selection<T>),
+    /// Selection was updated
+    Updated {
+        /// Old selection state
+        old: Selection<T>,
+        /// New selection state
+        new: Selection<T>,
+    },
+}
+
+impl<T> PartialEq for OwnedSelectionDelta<T> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (OwnedSelectionDelta::Created(a), OwnedSelectionDelta::Created(b))
+            | (OwnedSelectionDelta::Deleted(a), OwnedSelectionDelta::Deleted(b)) => a == b,
+            (
+                OwnedSelectionDelta::Updated { old, new },
+                OwnedSelectionDelta::Updated {
+                    old: other_old,
+                    new: other_new,
+                },
+            ) => old == other_old && new == other_new,
+            _ => false,
+        }
+    }
+}
+
+impl<T> Eq for OwnedSelectionDelta<T> {}
+
+impl<T> PartialOrd for OwnedSelectionDelta<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for OwnedSelectionDelta<T> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        // Deltas are ordered by `from` and variant, the rest of states only tells
+        // distinct deltas apart, so equal ordering means equal deltas:
+        self.get_from_position()
+            .cmp(other.get_from_position())
+            .then_with(|| self.variant_order().cmp(&other.variant_order()))
+            .then_with(|| match (self, other) {
+                (
+                    OwnedSelectionDelta::Updated { old, new },
+                    OwnedSelectionDelta::Updated {
+                        old: other_old,
+                        new: other_new,
+                    },
+                ) => cmp_states(old, other_old).then_with(|| cmp_states(new, other_new)),
+                (
+                    OwnedSelectionDelta::Created(a) | OwnedSelectionDelta::Deleted(a),
+                    OwnedSelectionDelta::Created(b) | OwnedSelectionDelta::Deleted(b),
+                ) => cmp_states(a, b),
+                _ => cmp::Ordering::Equal,
+            })
+    }
+}
+
+/// Total ordering of selection states consistent with their equality.
+fn cmp_states<T>(a: &Selection<T>, b: &Selection<T>) -> cmp::Ordering {
+    let key = |s: &Selection<T>| {
+        (
+            s.from.line,
+            s.from.column,
+            s.from.sticky_column,
+            s.to.line,
+            s.to.column,
+            s.to.sticky_column,
+            s.direction == SelectionDirection::Backward,
+        )
+    };
+    key(a).cmp(&key(b))
+}
+
+impl<T> OwnedSelectionDelta<T> {
+    /// Shortcut to get `from` coordinate required for comparison
+    fn get_from_position(&self) -> &Position {
+        match self {
+            OwnedSelectionDelta::Created(s)
+            | OwnedSelectionDelta::Deleted(s)
+            | OwnedSelectionDelta::Updated { old: s, .. } => &s.from,
+        }
+    }
+
+    /// Rank of a variant for deltas with the same `from`, `Deleted` goes first
+    /// just like for [SelectionDelta].
+    fn variant_order(&self) -> u8 {
+        match self {
+            OwnedSelectionDelta::Deleted(_) => 0,
+            OwnedSelectionDelta::Updated { .. } => 1,
+            OwnedSelectionDelta::Created(_) => 2,
+        }
+    }
+
+    /// Returns a delta that reverts this one: `Created` becomes `Deleted` and
+    /// vice versa, `Updated` states are swapped.
+    pub fn invert(self) -> Self {
+        match self {
+            OwnedSelectionDelta::Created(s) => OwnedSelectionDelta::Deleted(s),
+            OwnedSelectionDelta::Deleted(s) => OwnedSelectionDelta::Created(s),
+            OwnedSelectionDelta::Updated { old, new } => {
+                OwnedSelectionDelta::Updated { old: new, new: old }
+            }
+        }
+    }
+}
+
+impl<T: Clone> From<SelectionDelta<'_, T>> for OwnedSelectionDelta<T> {
+    fn from(delta: SelectionDelta<'_, T>) -> Self {
+        delta.into_owned()
+    }
+}
+
+/// Storage indices of a changed selection, see
+/// [SelectionStorage::set_delta_indices](crate::SelectionStorage::set_delta_indices).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeltaIndices {
+    /// Index of the old state before the change, `None` for `Created`.
+    pub old: Option<usize>,
+    /// Index of the new state after the change, `None` for `Deleted`.
+    pub new: Option<usize>,
+}
+
+/// Record of a selection absorbed by another one on merge, the absorbed
+/// selection is reported as `Deleted` too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelectionMerge {
+    /// Id of the absorbed selection.
+    pub absorbed: SelectionId,
+    /// Id of the selection that absorbed it.
+    pub into: SelectionId,
+}
+
 /// Collection of sorted selection deltas.
 /// Works better when pushing deltas in order (ordered by `from`, `Deleted`
 /// first on collision).
 #[derive(Debug)]
-pub struct SelectionDeltas<'a> {
-    deltas: UniqueSortedVec<SelectionDelta<'a>>,
+pub struct SelectionDeltas<'a, T = ()> {
+    deltas: UniqueSortedVec<SelectionDelta<'a, T>>,
+    /// Storage indices for each delta in the same order, if requested.
+    indices: Option<Vec<DeltaIndices>>,
+    /// Selections absorbed on merge.
+    merges: Vec<SelectionMerge>,
 }
 
-impl Default for SelectionDeltas<'_> {
+impl<T> Default for SelectionDeltas<'_, T> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<'a> SelectionDeltas<'a> {
+impl<'a, T> SelectionDeltas<'a, T> {
     /// Create empty deltas collection
     pub(crate) fn new() -> Self {
         SelectionDeltas {
             deltas: UniqueSortedVec::new(),
+            indices: Some(Vec::new()),
+            merges: Vec::new(),
         }
     }
 
@@ -79,28 +285,103 @@ impl<'a> SelectionDeltas<'a> {
     pub(crate) fn with_capacity(n: usize) -> Self {
         SelectionDeltas {
             deltas: UniqueSortedVec::with_capacity(n),
+            indices: Some(Vec::with_capacity(n)),
+            merges: Vec::new(),
+        }
+    }
+
+    /// Adds a delta keeping its indices in the same order.
+    fn push(&mut self, delta: SelectionDelta<'a, T>, indices: DeltaIndices) {
+        if let (Some(idx), Some(all)) = (self.deltas.push(delta), self.indices.as_mut()) {
+            all.insert(idx, indices);
+        }
+    }
+
+    /// Adds delta for a deleted selection which was on `old_idx`
+    pub(crate) fn push_deleted(&mut self, s: Selection<T>, old_idx: usize) {
+        self.push(
+            SelectionDelta::Deleted(s),
+            DeltaIndices {
+                old: Some(old_idx),
+                new: None,
+            },
+        );
+    }
+
+    /// Adds delta for a selection which was on `old_idx` and is deleted
+    /// because it was absorbed by a selection identified by `into`
+    pub(crate) fn push_merged(
+        &mut self,
+        s: Selection<T>,
+        old_idx: usize,
+        into: Option<SelectionId>,
+    ) {
+        if let (Some(absorbed), Some(into)) = (s.id, into) {
+            self.merges.push(SelectionMerge { absorbed, into });
         }
+        self.push_deleted(s, old_idx);
+    }
+
+    /// Adds delta for a created selection on `new_idx`
+    pub(crate) fn push_created(&mut self, s: &'a Selection<T>, new_idx: usize) {
+        self.push(
+            SelectionDelta::Created(s),
+            DeltaIndices {
+                old: None,
+                new: Some(new_idx),
+            },
+        );
+    }
+
+    /// Adds delta for a selection updated from `old` state on `old_idx` to
+    /// `new` one on `new_idx`
+    pub(crate) fn push_updated(
+        &mut self,
+        old: Selection<T>,
+        old_idx: usize,
+        new: &'a Selection<T>,
+        new_idx: usize,
+    ) {
+        self.push(
+            SelectionDelta::Updated { old, new },
+            DeltaIndices {
+                old: Some(old_idx),
+                new: Some(new_idx),
+            },
+        );
+    }
+
+    /// Sets merges of detached deltas borrowed again.
+    pub(crate) fn set_merges(&mut self, merges: Vec<SelectionMerge>) {
+        self.merges = merges;
+    }
+
+    /// Drops storage indices if they weren't asked for.
+    pub(crate) fn strip_indices(&mut self) {
+        self.indices = None;
     }
 
-    /// Adds delta for a deleted selection
-    pub(crate) fn push_deleted(&mut self, s: Selection) {
-        self.deltas.push(SelectionDelta::Deleted(s));
+    /// Returns iterator over selection deltas keeping their order.
+    pub fn iter(&self) -> slice::Iter<'_, SelectionDelta<'a, T>> {
+        self.deltas.iter()
     }
 
-    /// Adds delta for a created selection
-    pub(crate) fn push_created(&mut self, s: &'a Selection) {
-        self.deltas.push(SelectionDelta::Created(s));
+    /// Returns storage indices of changed selections in the same order as
+    /// deltas, if the storage was asked to provide them.
+    pub fn indices(&self) -> Option<&[DeltaIndices]> {
+        self.indices.as_deref()
     }
 
-    /// Adds delta for an updated selection
-    pub(crate) fn push_updated(&mut self, old: Selection, new: &'a Selection) {
-        self.deltas.push(SelectionDelta::Updated { old, new });
+    /// Returns ids of selections absorbed by other selections, these are
+    /// reported as `Deleted` too.
+    pub fn merges(&self) -> &[SelectionMerge] {
+        &self.merges
     }
 }
 
-impl<'a> IntoIterator for SelectionDeltas<'a> {
-    type IntoIter = vec::IntoIter<SelectionDelta<'a>>;
-    type Item = SelectionDelta<'a>;
+impl<'a, T> IntoIterator for SelectionDeltas<'a, T> {
+    type IntoIter = vec::IntoIter<SelectionDelta<'a, T>>;
+    type Item = SelectionDelta<'a, T>;
 
     /// Returns iterator over selection deltas keeping their order (in case of
     /// `Updated` it will order by its old state)
@@ -108,3 +389,250 @@ impl<'a> IntoIterator for SelectionDeltas<'a> {
         self.deltas.take().into_iter()
     }
 }
+
+impl<T: Clone> SelectionDeltas<'_, T> {
+    /// Clones borrowed selections to detach deltas from selections storage,
+    /// order is kept.
+    pub fn into_owned(self) -> OwnedSelectionDeltas<T> {
+        self.into_owned_with(Selection::clone)
+    }
+}
+
+impl<T> SelectionDeltas<'_, T> {
+    /// Detaches deltas from selections storage cloning borrowed selections
+    /// with `clone`, order is kept.
+    pub(crate) fn into_owned_with(self, clone: CloneSelection<T>) -> OwnedSelectionDeltas<T> {
+        OwnedSelectionDeltas {
+            indices: self.indices,
+            merges: self.merges,
+            deltas: self
+                .deltas
+                .take()
+                .into_iter()
+                .map(|delta| delta.into_owned_with(clone))
+                .collect(),
+        }
+    }
+}
+
+/// Collection of sorted owned selection deltas, see [OwnedSelectionDelta].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedSelectionDeltas<T = ()> {
+    deltas: Vec<OwnedSelectionDelta<T>>,
+    /// Storage indices for each delta in the same order, if requested.
+    indices: Option<Vec<DeltaIndices>>,
+    /// Selections absorbed on merge.
+    merges: Vec<SelectionMerge>,
+}
+
+impl<T> Default for OwnedSelectionDeltas<T> {
+    fn default() -> Self {
+        OwnedSelectionDeltas {
+            deltas: Vec::new(),
+            indices: None,
+            merges: Vec::new(),
+        }
+    }
+}
+
+impl<T> OwnedSelectionDeltas<T> {
+    /// Returns iterator over selection deltas keeping their order.
+    pub fn iter(&self) -> slice::Iter<'_, OwnedSelectionDelta<T>> {
+        self.deltas.iter()
+    }
+
+    /// Returns a number of deltas.
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    /// Checks if there are no deltas.
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    /// Returns storage indices of changed selections, see
+    /// [SelectionDeltas::indices].
+    pub fn indices(&self) -> Option<&[DeltaIndices]> {
+        self.indices.as_deref()
+    }
+
+    /// Drops storage indices if they weren't asked for.
+    pub(crate) fn strip_indices(&mut self) {
+        self.indices = None;
+    }
+
+    /// Clones deltas cloning selections with `clone`.
+    pub(crate) fn clone_with(&self, clone: CloneSelection<T>) -> Self {
+        OwnedSelectionDeltas {
+            deltas: self
+                .deltas
+                .iter()
+                .map(|delta| match delta {
+                    OwnedSelectionDelta::Created(s) => OwnedSelectionDelta::Created(clone(s)),
+                    OwnedSelectionDelta::Deleted(s) => OwnedSelectionDelta::Deleted(clone(s)),
+                    OwnedSelectionDelta::Updated { old, new } => OwnedSelectionDelta::Updated {
+                        old: clone(old),
+                        new: clone(new),
+                    },
+                })
+                .collect(),
+            indices: self.indices.clone(),
+            merges: self.merges.clone(),
+        }
+    }
+
+    /// Returns ids of selections absorbed by other selections, see
+    /// [SelectionDeltas::merges].
+    pub fn merges(&self) -> &[SelectionMerge] {
+        &self.merges
+    }
+
+    /// Returns deltas that revert these ones, see
+    /// [OwnedSelectionDelta::invert]. Indices are swapped the same way, merges
+    /// are not reverted.
+    pub fn invert(self) -> Self {
+        let mut deltas = UniqueSortedVec::with_capacity(self.deltas.len());
+        let mut indices = self.indices.as_ref().map(|i| Vec::with_capacity(i.len()));
+        for (n, delta) in self.deltas.into_iter().enumerate() {
+            let idx = deltas.push(delta.invert());
+            if let (Some(idx), Some(inverted), Some(original)) =
+                (idx, indices.as_mut(), self.indices.as_ref())
+            {
+                let DeltaIndices { old, new } = original[n];
+                inverted.insert(idx, DeltaIndices { old: new, new: old });
+            }
+        }
+        OwnedSelectionDeltas {
+            deltas: deltas.take(),
+            indices,
+            merges: Vec::new(),
+        }
+    }
+}
+
+impl<T: Clone> From<SelectionDeltas<'_, T>> for OwnedSelectionDeltas<T> {
+    fn from(deltas: SelectionDeltas<'_, T>) -> Self {
+        deltas.into_owned()
+    }
+}
+
+impl<T> IntoIterator for OwnedSelectionDeltas<T> {
+    type IntoIter = vec::IntoIter<OwnedSelectionDelta<T>>;
+    type Item = OwnedSelectionDelta<T>;
+
+    /// Returns iterator over selection deltas keeping their order (in case of
+    /// `Updated` it will order by its old state)
+    fn into_iter(self) -> Self::IntoIter {
+        self.deltas.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a OwnedSelectionDeltas<T> {
+    type IntoIter = slice::Iter<'a, OwnedSelectionDelta<T>>;
+    type Item = &'a OwnedSelectionDelta<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.deltas.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::SelectionStorage;
+
+    #[test]
+    fn owned_delta_ord() {
+        let selection = Selection::new(Position::new(1, 3), Position::new(3, 7));
+        let created = OwnedSelectionDelta::Created(selection.clone());
+        let deleted = OwnedSelectionDelta::Deleted(selection.clone());
+        assert_eq!(created.cmp(&created.clone()), cmp::Ordering::Equal);
+        assert_eq!(deleted.cmp(&deleted.clone()), cmp::Ordering::Equal);
+        assert_eq!(deleted.cmp(&created), cmp::Ordering::Less);
+        assert_eq!(created.cmp(&deleted), cmp::Ordering::Greater);
+
+        let longer =
+            OwnedSelectionDelta::Deleted(Selection::new(Position::new(1, 3), Position::new(4, 0)));
+        assert_eq!(deleted.cmp(&longer), cmp::Ordering::Less);
+        let reversed =
+            OwnedSelectionDelta::Deleted(Selection::new(Position::new(3, 7), Position::new(1, 3)));
+        assert_ne!(deleted.cmp(&reversed), cmp::Ordering::Equal);
+    }
+
+    #[test]
+    fn into_owned_keeps_order() {
+        let mut storage = SelectionStorage::new();
+        storage.insert(Selection::new(Position::new(1, 3), Position::new(3, 7)));
+        storage.insert(Selection::new(Position::new(4, 3), Position::new(5, 7)));
+        let deltas = storage
+            .insert(Selection::new(Position::new(3, 5), Position::new(4, 7)))
+            .into_owned();
+
+        // Storage is not borrowed anymore:
+        storage.insert(Selection::new(Position::new(10, 0), Position::new(10, 1)));
+
+        assert_eq!(
+            deltas.into_iter().collect::<Vec<_>>(),
+            [
+                OwnedSelectionDelta::Deleted(Selection::new(
+                    Position::new(1, 3),
+                    Position::new(3, 7)
+                )),
+                OwnedSelectionDelta::Created(Selection::new(
+                    Position::new(1, 3),
+                    Position::new(5, 7)
+                )),
+                OwnedSelectionDelta::Deleted(Selection::new(
+                    Position::new(4, 3),
+                    Position::new(5, 7)
+                )),
+            ]
+        );
+    }
+
+    #[test]
+    fn owned_ordering() {
+        let deleted =
+            OwnedSelectionDelta::Deleted(Selection::new(Position::new(1, 3), Position::new(3, 7)));
+        let created =
+            OwnedSelectionDelta::Created(Selection::new(Position::new(1, 3), Position::new(1, 4)));
+        let updated = OwnedSelectionDelta::Updated {
+            old: Selection::new(Position::new(0, 3), Position::new(0, 4)),
+            new: Selection::new(Position::new(5, 3), Position::new(5, 4)),
+        };
+        assert!(deleted < created);
+        assert!(updated < deleted);
+    }
+
+    #[test]
+    fn invert() {
+        let mut storage = SelectionStorage::new();
+        storage.insert(Selection::new(Position::new(1, 3), Position::new(3, 7)));
+        let deltas = storage
+            .insert(Selection::new(Position::new(0, 3), Position::new(1, 5)))
+            .into_owned();
+
+        assert_eq!(
+            deltas.invert().into_iter().collect::<Vec<_>>(),
+            [
+                OwnedSelectionDelta::Deleted(Selection::new(
+                    Position::new(0, 3),
+                    Position::new(3, 7)
+                )),
+                OwnedSelectionDelta::Created(Selection::new(
+                    Position::new(1, 3),
+                    Position::new(3, 7)
+                )),
+            ]
+        );
+
+        let updated = OwnedSelectionDelta::Updated {
+            old: Selection::new(Position::new(0, 3), Position::new(0, 4)),
+            new: Selection::new(Position::new(5, 3), Position::new(5, 4)),
+        };
+        assert_eq!(updated.clone().invert().invert(), updated);
+    }
+}
@@ -0,0 +1,282 @@
+//! Accumulation of selection deltas of multiple operations into one net change
+//! list.
+
+use std::collections::{BTreeMap, HashSet};
+
+use super::{OwnedSelectionDelta, OwnedSelectionDeltas, SelectionMerge};
+use crate::{utils::UniqueSortedVec, Position, Selection};
+
+/// Selection changed during accumulation.
+#[derive(Debug)]
+struct Changed<T> {
+    /// Selection state before accumulation, `None` if it was created.
+    origin: Option<Selection<T>>,
+    /// Current selection state.
+    current: Selection<T>,
+}
+
+/// Merges deltas of successive operations on the same
+/// [SelectionStorage](crate::SelectionStorage), so the result describes a net
+/// change between the state before the first operation and after the last
+/// one:
+/// - `Created` then `Updated` becomes `Created`;
+/// - `Created` then `Deleted` disappears;
+/// - `Updated` then `Updated` becomes one `Updated` from the first old state to
+///   the last new state;
+/// - `Updated` then `Deleted` becomes `Deleted` of the first old state;
+/// - changes that result in the original state disappear.
+///
+/// Merges are kept only for selections deleted in the net change and point to
+/// the last selection in a chain of merges. Storage indices are not kept, as
+/// they don't survive successive operations.
+#[derive(Debug)]
+pub struct DeltasAccumulator<T = ()> {
+    /// Changed selections by their current `from` position.
+    changed: BTreeMap<Position, Changed<T>>,
+    /// Deleted selections in their original state by their `from` position.
+    deleted: BTreeMap<Position, Selection<T>>,
+    /// Merges in order they happened.
+    merges: Vec<SelectionMerge>,
+}
+
+impl<T> Default for DeltasAccumulator<T> {
+    fn default() -> Self {
+        DeltasAccumulator {
+            changed: BTreeMap::new(),
+            deleted: BTreeMap::new(),
+            merges: Vec::new(),
+        }
+    }
+}
+
+impl<T> DeltasAccumulator<T> {
+    /// Create empty accumulator.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Checks if no deltas were accumulated.
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.deleted.is_empty()
+    }
+
+    /// Adds deltas of one operation.
+    pub fn push(&mut self, deltas: impl Into<OwnedSelectionDeltas<T>>) {
+        // Within one operation a new selection may take a place of an old one, so all
+        // removals are handled first.
+        let deltas = deltas.into();
+        self.merges.extend_from_slice(&deltas.merges);
+        let mut added = Vec::new();
+        for delta in deltas {
+            match delta {
+                OwnedSelectionDelta::Created(s) => added.push(Changed {
+                    origin: None,
+                    current: s,
+                }),
+                OwnedSelectionDelta::Deleted(s) => match self.changed.remove(&s.from) {
+                    Some(Changed {
+                        origin: Some(origin),
+                        ..
+                    }) => {
+                        self.deleted.insert(origin.from.clone(), origin);
+                    }
+                    Some(Changed { origin: None, .. }) => {}
+                    None => {
+                        self.deleted.insert(s.from.clone(), s);
+                    }
+                },
+                OwnedSelectionDelta::Updated { old, new } => {
+                    let origin = match self.changed.remove(&old.from) {
+                        Some(changed) => changed.origin,
+                        None => Some(old),
+                    };
+                    added.push(Changed {
+                        origin,
+                        current: new,
+                    });
+                }
+            }
+        }
+
+        for changed in added {
+            self.changed.insert(changed.current.from.clone(), changed);
+        }
+    }
+
+    /// Takes accumulated net deltas leaving the accumulator empty.
+    pub fn take(&mut self) -> OwnedSelectionDeltas<T> {
+        let mut deleted = std::mem::take(&mut self.deleted);
+        let mut deltas = UniqueSortedVec::with_capacity(self.changed.len() + deleted.len());
+
+        for Changed { origin, current } in std::mem::take(&mut self.changed).into_values() {
+            match origin {
+                Some(old) if old == current => {}
+                Some(old) => {
+                    deltas.push(OwnedSelectionDelta::Updated { old, new: current });
+                }
+                None => {
+                    // Deleted and created again is not a change at all:
+                    if deleted
+                        .get(&current.from)
+                        .is_some_and(|d| d == &current && d.id == current.id)
+                    {
+                        deleted.remove(&current.from);
+                    } else {
+                        deltas.push(OwnedSelectionDelta::Created(current));
+                    }
+                }
+            }
+        }
+
+        let merges = std::mem::take(&mut self.merges);
+        let deleted_ids: HashSet<_> = deleted.values().filter_map(|d| d.id).collect();
+        let mut net_merges = Vec::new();
+        for (idx, merge) in merges.iter().enumerate() {
+            let later = &merges[idx + 1..];
+            // Only the last merge of a selection matters:
+            if later.iter().any(|m| m.absorbed == merge.absorbed)
+                || !deleted_ids.contains(&merge.absorbed)
+            {
+                continue;
+            }
+            let mut into = merge.into;
+            for m in later {
+                if m.absorbed == into {
+                    into = m.into;
+                }
+            }
+            net_merges.push(SelectionMerge {
+                absorbed: merge.absorbed,
+                into,
+            });
+        }
+
+        for s in deleted.into_values() {
+            deltas.push(OwnedSelectionDelta::Deleted(s));
+        }
+
+        OwnedSelectionDeltas {
+            deltas: deltas.take(),
+            indices: None,
+            merges: net_merges,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        test_utils::{selection, TestLineLengths},
+        SelectionStorage,
+    };
+
+    #[test]
+    fn created_then_updated() {
+        let line_lengths = TestLineLengths::from_lengths([20, 20]);
+        let mut storage = SelectionStorage::new();
+        let mut acc = DeltasAccumulator::new();
+
+        acc.push(storage.insert(selection((1, 0), (1, 2))));
+        acc.push(storage.move_right_single(&line_lengths, &Position::new(1, 0), 3, false));
+        acc.push(storage.move_right_single(&line_lengths, &Position::new(1, 5), 1, true));
+
+        assert_eq!(
+            acc.take().into_iter().collect::<Vec<_>>(),
+            [OwnedSelectionDelta::Created(selection((1, 5), (1, 6)))]
+        );
+        assert!(acc.is_empty());
+    }
+
+    #[test]
+    fn created_then_deleted() {
+        let line_lengths = TestLineLengths::from_lengths([20, 20]);
+        let mut storage = SelectionStorage::new();
+        let mut acc = DeltasAccumulator::new();
+
+        acc.push(storage.insert(selection((0, 5), (0, 6))));
+        acc.push(storage.move_right_single(&line_lengths, &Position::new(0, 0), 6, true));
+
+        assert_eq!(
+            acc.take().into_iter().collect::<Vec<_>>(),
+            [OwnedSelectionDelta::Updated {
+                old: selection((0, 0), (0, 0)),
+                new: selection((0, 0), (0, 6)),
+            }]
+        );
+    }
+
+    #[test]
+    fn updated_then_updated_and_deleted() {
+        let line_lengths = TestLineLengths::from_lengths([20, 20]);
+        let mut storage = SelectionStorage::new();
+        storage.insert(selection((0, 5), (0, 6)));
+        storage.insert(selection((1, 5), (1, 6)));
+        let mut acc = DeltasAccumulator::new();
+
+        acc.push(storage.move_down_single(&line_lengths, &Position::new(0, 0), 1, false));
+        acc.push(storage.move_right_single(&line_lengths, &Position::new(1, 0), 1, false));
+        acc.push(storage.move_right_single(&line_lengths, &Position::new(0, 5), 1, false));
+        acc.push(storage.move_right_single(&line_lengths, &Position::new(1, 1), 4, false));
+
+        assert_eq!(
+            acc.take().into_iter().collect::<Vec<_>>(),
+            [
+                OwnedSelectionDelta::Updated {
+                    old: selection((0, 0), (0, 0)),
+                    new: selection((1, 5), (1, 5)),
+                },
+                OwnedSelectionDelta::Updated {
+                    old: selection((0, 5), (0, 6)),
+                    new: selection((0, 7), (0, 7)),
+                },
+                OwnedSelectionDelta::Deleted(selection((1, 5), (1, 6))),
+            ]
+        );
+    }
+
+    #[test]
+    fn back_to_original() {
+        let line_lengths = TestLineLengths::from_lengths([20, 20]);
+        let mut storage = SelectionStorage::new();
+        storage.insert(selection((0, 5), (0, 5)));
+        let mut acc = DeltasAccumulator::new();
+
+        acc.push(storage.move_right_single(&line_lengths, &Position::new(0, 5), 1, false));
+        acc.push(storage.move_left_single(&line_lengths, &Position::new(0, 6), 1, false));
+
+        assert!(acc.take().is_empty());
+    }
+
+    #[test]
+    fn recreated_is_not_original() {
+        let mut storage = SelectionStorage::new();
+        storage.insert(selection((0, 5), (0, 5)));
+        let mut acc = DeltasAccumulator::new();
+
+        acc.push(storage.insert(selection((0, 4), (0, 6))));
+        acc.push(storage.insert_replacing(selection((0, 5), (0, 5))));
+
+        // Same coordinates, but the selection has a new id now:
+        let deltas = acc.take();
+        assert_eq!(
+            deltas.iter().collect::<Vec<_>>(),
+            [
+                &OwnedSelectionDelta::Deleted(selection((0, 5), (0, 5))),
+                &OwnedSelectionDelta::Created(selection((0, 5), (0, 5))),
+            ]
+        );
+        let deleted_id = match deltas.iter().next() {
+            Some(OwnedSelectionDelta::Deleted(s)) => s.selection_id(),
+            _ => None,
+        };
+        assert_ne!(
+            deleted_id,
+            storage
+                .get(&Position::new(0, 5))
+                .and_then(Selection::selection_id)
+        );
+    }
+}
@@ -0,0 +1,96 @@
+//! Lines affected by selection deltas.
+
+use std::ops::Range;
+
+use super::{OwnedSelectionDelta, OwnedSelectionDeltas, SelectionDelta, SelectionDeltas};
+use crate::Selection;
+
+impl<T> SelectionDeltas<'_, T> {
+    /// Returns sorted non-overlapping ranges of lines that were covered by
+    /// changed selections either before or after the change, so only these
+    /// lines need to be redrawn.
+    pub fn dirty_lines(&self) -> Vec<Range<usize>> {
+        merge_lines(self.deltas.iter().flat_map(|delta| match delta {
+            SelectionDelta::Created(s) => [Some(*s), None],
+            SelectionDelta::Deleted(s) => [Some(s), None],
+            SelectionDelta::Updated { old, new } => [Some(old), Some(*new)],
+        }))
+    }
+}
+
+impl<T> OwnedSelectionDeltas<T> {
+    /// Returns sorted non-overlapping ranges of lines that were covered by
+    /// changed selections either before or after the change, so only these
+    /// lines need to be redrawn.
+    pub fn dirty_lines(&self) -> Vec<Range<usize>> {
+        merge_lines(self.deltas.iter().flat_map(|delta| match delta {
+            OwnedSelectionDelta::Created(s) | OwnedSelectionDelta::Deleted(s) => [Some(s), None],
+            OwnedSelectionDelta::Updated { old, new } => [Some(old), Some(new)],
+        }))
+    }
+}
+
+/// Merges lines ranges of `selections` into sorted ranges with no overlaps,
+/// adjacent ranges are merged too.
+fn merge_lines<'a, T: 'a>(
+    selections: impl Iterator<Item = Option<&'a Selection<T>>>,
+) -> Vec<Range<usize>> {
+    let mut lines: Vec<Range<usize>> = selections
+        .flatten()
+        .map(|s| s.from.line..s.to.line + 1)
+        .collect();
+    lines.sort_unstable_by_key(|range| range.start);
+
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(lines.len());
+    for range in lines {
+        match merged.last_mut() {
+            Some(last) if last.end >= range.start => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::{test_utils::TestLineLengths, Position, Selection, SelectionStorage};
+
+    #[test]
+    fn dirty_lines() {
+        let mut line_lengths = TestLineLengths::new();
+        for line in 0..20 {
+            line_lengths.set(line, 10);
+        }
+        let mut storage = SelectionStorage::new();
+        storage.insert(Selection::new(Position::new(3, 0), Position::new(4, 3)));
+        storage.insert(Selection::new(Position::new(10, 0), Position::new(10, 3)));
+
+        assert_eq!(
+            storage
+                .move_down_single(&line_lengths, &Position::new(0, 0), 1, false)
+                .dirty_lines(),
+            vec![(0..2)]
+        );
+        assert_eq!(
+            storage
+                .move_down_single(&line_lengths, &Position::new(10, 0), 5, false)
+                .dirty_lines(),
+            [10..11, 15..16]
+        );
+        assert_eq!(
+            storage
+                .insert(Selection::new(Position::new(2, 0), Position::new(5, 1)))
+                .into_owned()
+                .dirty_lines(),
+            vec![(2..6)]
+        );
+        assert_eq!(
+            storage
+                .move_up_single(&line_lengths, &Position::new(1, 0), 1, true)
+                .dirty_lines(),
+            vec![(0..2)]
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Selections history to undo and redo selection changes.
+
+use crate::{DeltasAccumulator, OwnedSelectionDeltas, SelectionDeltas, SelectionStorage};
+
+/// History of [SelectionStorage] changes grouped into steps. Deltas of each
+/// operation should be recorded, and then the whole step could be undone
+/// (reverted) or redone.
+#[derive(Debug)]
+pub struct SelectionHistory<T = ()> {
+    /// Finished steps to undo, the last one is the latest.
+    undo: Vec<OwnedSelectionDeltas<T>>,
+    /// Undone steps to redo, the last one is the latest undone.
+    redo: Vec<OwnedSelectionDeltas<T>>,
+    /// Step in progress.
+    current: DeltasAccumulator<T>,
+}
+
+impl<T> Default for SelectionHistory<T> {
+    fn default() -> Self {
+        SelectionHistory {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            current: DeltasAccumulator::new(),
+        }
+    }
+}
+
+impl<T: Clone> SelectionHistory<T> {
+    /// Create empty history.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Records deltas of an operation as a part of current step. Undone steps
+    /// cannot be redone after that.
+    pub fn record(&mut self, deltas: impl Into<OwnedSelectionDeltas<T>>) {
+        let deltas = deltas.into();
+        if !deltas.is_empty() {
+            self.redo.clear();
+            self.current.push(deltas);
+        }
+    }
+
+    /// Finishes current step, so the following operations will be undone
+    /// separately. Does nothing if there were no changes.
+    pub fn commit_step(&mut self) {
+        let step = self.current.take();
+        if !step.is_empty() {
+            self.undo.push(step);
+        }
+    }
+
+    /// Checks if there is a step to undo, including unfinished one.
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty() || !self.current.is_empty()
+    }
+
+    /// Checks if there is a step to redo.
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Reverts the latest step (finishing current one first) applying inverted
+    /// deltas to the `storage`. Returns `None` if there is nothing to undo.
+    pub fn undo_selection<'a>(
+        &mut self,
+        storage: &'a mut SelectionStorage<T>,
+    ) -> Option<SelectionDeltas<'a, T>> {
+        self.commit_step();
+        let step = self.undo.pop()?;
+        let inverted = step.clone().invert();
+        self.redo.push(step);
+        Some(storage.apply_deltas(&inverted))
+    }
+
+    /// Applies the latest undone step to the `storage` again. Returns `None` if
+    /// there is nothing to redo.
+    pub fn redo_selection<'a>(
+        &mut self,
+        storage: &'a mut SelectionStorage<T>,
+    ) -> Option<SelectionDeltas<'a, T>> {
+        let step = self.redo.pop()?;
+        self.undo.push(step);
+        self.undo.last().map(|step| storage.apply_deltas(step))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{test_utils::TestLineLengths, Position, Selection};
+
+    fn state(storage: &SelectionStorage) -> Vec<Selection> {
+        storage.iter_all().cloned().collect()
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut line_lengths = TestLineLengths::new();
+        line_lengths.set(0, 20);
+        line_lengths.set(1, 20);
+        let mut storage = SelectionStorage::new();
+        let mut history = SelectionHistory::new();
+        let initial = state(&storage);
+
+        history.record(storage.insert(Selection::new(Position::new(1, 0), Position::new(1, 3))));
+        history.record(storage.move_right_single(&line_lengths, &Position::new(0, 0), 2, false));
+        history.commit_step();
+        let after_first_step = state(&storage);
+
+        history.record(storage.move_down_single(&line_lengths, &Position::new(0, 2), 1, true));
+        let after_second_step = state(&storage);
+        assert_ne!(after_first_step, after_second_step);
+
+        assert!(history.undo_selection(&mut storage).is_some());
+        assert_eq!(state(&storage), after_first_step);
+        assert!(history.undo_selection(&mut storage).is_some());
+        assert_eq!(state(&storage), initial);
+        assert!(history.undo_selection(&mut storage).is_none());
+        assert!(!history.can_undo());
+
+        assert!(history.redo_selection(&mut storage).is_some());
+        assert_eq!(state(&storage), after_first_step);
+        assert!(history.redo_selection(&mut storage).is_some());
+        assert_eq!(state(&storage), after_second_step);
+        assert!(history.redo_selection(&mut storage).is_none());
+    }
+
+    #[test]
+    fn record_drops_redo() {
+        let mut line_lengths = TestLineLengths::new();
+        line_lengths.set(0, 20);
+        let mut storage = SelectionStorage::new();
+        let mut history = SelectionHistory::new();
+
+        history.record(storage.move_right_single(&line_lengths, &Position::new(0, 0), 2, false));
+        history.undo_selection(&mut storage);
+        assert!(history.can_redo());
+
+        history.record(storage.move_right_single(&line_lengths, &Position::new(0, 0), 3, false));
+        assert!(!history.can_redo());
+        assert!(history.redo_selection(&mut storage).is_none());
+        assert_eq!(
+            state(&storage),
+            [Selection::new(Position::new(0, 3), Position::new(0, 3))]
+        );
+    }
+}
@@ -0,0 +1,280 @@
+//! [IntervalStorage] declaration and implementation.
+
+use std::ops::Range;
+
+use crate::Position;
+
+/// Pair of coordinates in a document with some value attached, both ends are
+/// inclusive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interval<T = ()> {
+    pub(crate) from: Position,
+    pub(crate) to: Position,
+    pub(crate) value: T,
+}
+
+impl<T> Interval<T> {
+    /// Build new interval from two positions in any order.
+    pub fn new(a: Position, b: Position, value: T) -> Self {
+        let (from, to) = if a > b { (b, a) } else { (a, b) };
+        Interval { from, to, value }
+    }
+
+    /// Get interval's beginning.
+    #[inline]
+    pub fn from(&self) -> &Position {
+        &self.from
+    }
+
+    /// Get interval's end.
+    #[inline]
+    pub fn to(&self) -> &Position {
+        &self.to
+    }
+
+    /// Get value attached to the interval.
+    #[inline]
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+}
+
+/// Storage of intervals which, unlike
+/// [SelectionStorage](crate::SelectionStorage), are allowed to overlap with
+/// each other, so it suits search hits highlights, diagnostics and so on.
+///
+/// Intervals are kept sorted by `from` position and form an implicit
+/// augmented interval tree: the middle of each index range is a root of its
+/// halves, so overlap queries skip whole subtrees which end before the query.
+#[derive(Debug)]
+pub struct IntervalStorage<T = ()> {
+    /// Intervals sorted by `from` position.
+    intervals: Vec<Interval<T>>,
+    /// Maximal `to` position within a subtree rooted at the same index, as
+    /// `to` positions are not sorted because of overlaps.
+    max_to: Vec<Position>,
+}
+
+impl<T> Default for IntervalStorage<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> IntervalStorage<T> {
+    /// Create empty interval storage.
+    pub fn new() -> Self {
+        IntervalStorage {
+            intervals: Vec::new(),
+            max_to: Vec::new(),
+        }
+    }
+
+    /// Returns a number of intervals stored.
+    pub fn len(&self) -> usize {
+        self.intervals.len()
+    }
+
+    /// Checks if there are no intervals stored.
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// Insert an interval, no matter if it overlaps with others.
+    pub fn insert(&mut self, interval: Interval<T>) {
+        let idx = self.intervals.partition_point(|i| i.from <= interval.from);
+        self.intervals.insert(idx, interval);
+        // Insertion shifts intervals and so roots of subtrees, the tree is rebuilt
+        // which is linear as the insertion itself:
+        self.rebuild_max_to();
+    }
+
+    /// Retains only intervals specified by the predicate.
+    pub fn retain(&mut self, f: impl FnMut(&Interval<T>) -> bool) {
+        self.intervals.retain(f);
+        self.rebuild_max_to();
+    }
+
+    /// Removes all intervals.
+    pub fn clear(&mut self) {
+        self.intervals.clear();
+        self.max_to.clear();
+    }
+
+    /// Returns iterator over all intervals ordered by their `from` position.
+    pub fn iter_all(&self) -> impl Iterator<Item = &Interval<T>> {
+        self.intervals.iter()
+    }
+
+    /// Returns iterator over intervals containing `position` (stab query).
+    pub fn iter_at<'a>(&'a self, position: &'a Position) -> impl Iterator<Item = &'a Interval<T>> {
+        self.iter_overlapping(position, position)
+    }
+
+    /// Returns iterator over intervals overlapping with a range from `from` to
+    /// `to` inclusive, ordered by their `from` position.
+    pub fn iter_overlapping<'a>(
+        &'a self,
+        from: &'a Position,
+        to: &'a Position,
+    ) -> impl Iterator<Item = &'a Interval<T>> {
+        let mut indicies = Vec::new();
+        self.find_overlapping_indicies(0..self.intervals.len(), from, to, &mut indicies);
+        indicies.into_iter().map(|idx| &self.intervals[idx])
+    }
+
+    /// Collects indicies of intervals overlapping with one from `from` to
+    /// `to` within a subtree of `range`, in order.
+    fn find_overlapping_indicies(
+        &self,
+        range: Range<usize>,
+        from: &Position,
+        to: &Position,
+        indicies: &mut Vec<usize>,
+    ) {
+        if range.is_empty() {
+            return;
+        }
+        let root = range.start + range.len() / 2;
+        // Nothing in the subtree reaches the query's beginning:
+        if &self.max_to[root] < from {
+            return;
+        }
+        self.find_overlapping_indicies(range.start..root, from, to, indicies);
+        // The root and everything after it start after the query's end:
+        if &self.intervals[root].from > to {
+            return;
+        }
+        if &self.intervals[root].to >= from {
+            indicies.push(root);
+        }
+        self.find_overlapping_indicies(root + 1..range.end, from, to, indicies);
+    }
+
+    /// Restores `max_to` invariant for all intervals.
+    fn rebuild_max_to(&mut self) {
+        self.max_to = self.intervals.iter().map(|i| i.to.clone()).collect();
+        self.fix_max_to(0..self.intervals.len());
+    }
+
+    /// Restores `max_to` invariant within a subtree of `range`, returns index
+    /// of its root.
+    fn fix_max_to(&mut self, range: Range<usize>) -> Option<usize> {
+        if range.is_empty() {
+            return None;
+        }
+        let root = range.start + range.len() / 2;
+        let children = [
+            self.fix_max_to(range.start..root),
+            self.fix_max_to(root + 1..range.end),
+        ];
+        for child in children.into_iter().flatten() {
+            if self.max_to[child] > self.max_to[root] {
+                self.max_to[root] = self.max_to[child].clone();
+            }
+        }
+        Some(root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn storage() -> IntervalStorage<&'static str> {
+        let mut storage = IntervalStorage::new();
+        storage.insert(Interval::new(Position::new(2, 0), Position::new(2, 5), "c"));
+        storage.insert(Interval::new(Position::new(0, 0), Position::new(5, 0), "a"));
+        storage.insert(Interval::new(Position::new(1, 3), Position::new(1, 7), "b"));
+        storage.insert(Interval::new(Position::new(2, 3), Position::new(2, 4), "d"));
+        storage.insert(Interval::new(Position::new(7, 1), Position::new(6, 0), "e"));
+        storage
+    }
+
+    fn values<'a>(iter: impl Iterator<Item = &'a Interval<&'static str>>) -> Vec<&'static str> {
+        iter.map(|i| *i.value()).collect()
+    }
+
+    #[test]
+    fn insert_keeps_order_and_overlaps() {
+        let storage = storage();
+        assert_eq!(values(storage.iter_all()), ["a", "b", "c", "d", "e"]);
+        assert_eq!(storage.len(), 5);
+    }
+
+    #[test]
+    fn iter_at() {
+        let storage = storage();
+        assert_eq!(
+            values(storage.iter_at(&Position::new(2, 3))),
+            ["a", "c", "d"]
+        );
+        assert_eq!(values(storage.iter_at(&Position::new(1, 7))), ["a", "b"]);
+        assert_eq!(
+            values(storage.iter_at(&Position::new(5, 1))),
+            Vec::<&str>::new()
+        );
+        assert_eq!(values(storage.iter_at(&Position::new(7, 1))), ["e"]);
+        assert_eq!(
+            values(storage.iter_at(&Position::new(8, 0))),
+            Vec::<&str>::new()
+        );
+    }
+
+    #[test]
+    fn iter_overlapping() {
+        let storage = storage();
+        assert_eq!(
+            values(storage.iter_overlapping(&Position::new(1, 8), &Position::new(2, 1))),
+            ["a", "c"]
+        );
+        assert_eq!(
+            values(storage.iter_overlapping(&Position::new(5, 0), &Position::new(6, 0))),
+            ["a", "e"]
+        );
+        assert_eq!(
+            values(storage.iter_overlapping(&Position::new(5, 1), &Position::new(5, 10))),
+            Vec::<&str>::new()
+        );
+    }
+
+    #[test]
+    fn retain() {
+        let mut storage = storage();
+        storage.retain(|i| *i.value() != "a");
+        assert_eq!(values(storage.iter_all()), ["b", "c", "d", "e"]);
+        assert_eq!(
+            values(storage.iter_at(&Position::new(3, 0))),
+            Vec::<&str>::new()
+        );
+        assert_eq!(values(storage.iter_at(&Position::new(2, 4))), ["c", "d"]);
+    }
+
+    #[test]
+    fn long_interval() {
+        let mut storage = IntervalStorage::new();
+        storage.insert(Interval::new(
+            Position::new(0, 0),
+            Position::new(100, 0),
+            "long",
+        ));
+        for line in 1..100 {
+            storage.insert(Interval::new(
+                Position::new(line, 0),
+                Position::new(line, 1),
+                "short",
+            ));
+        }
+        assert_eq!(
+            values(storage.iter_at(&Position::new(50, 1))),
+            ["long", "short"]
+        );
+        assert_eq!(values(storage.iter_at(&Position::new(50, 2))), ["long"]);
+        assert_eq!(
+            values(storage.iter_overlapping(&Position::new(98, 1), &Position::new(101, 0))),
+            ["long", "short", "short"]
+        );
+    }
+}
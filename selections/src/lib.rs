@@ -3,17 +3,31 @@
 #![deny(missing_docs)]
 
 mod deltas;
+mod history;
+mod intervals;
 mod position;
 mod selection;
 mod storage;
 #[cfg(test)]
 mod test_utils;
+mod text;
 mod utils;
 
-pub use deltas::{SelectionDelta, SelectionDeltas};
+use std::borrow::Cow;
+
+pub use deltas::{
+    DeltaIndices, DeltasAccumulator, OwnedSelectionDelta, OwnedSelectionDeltas, SelectionDelta,
+    SelectionDeltas, SelectionMerge,
+};
+pub use history::SelectionHistory;
+pub use intervals::{Interval, IntervalStorage};
 pub use position::Position;
-pub use selection::{Selection, SelectionDirection};
-pub use storage::SelectionStorage;
+pub use selection::{Selection, SelectionDirection, SelectionId};
+pub use storage::{
+    CollisionConfig, CollisionPolicy, LineCaret, LineHighlights, LineSpan, LineSpans, Matcher,
+    ObserverId, PasteMode, SelectionKey, SelectionStorage, SyntaxNodeTarget, SyntaxTree, TextEdit,
+};
+pub use text::TextObject;
 
 /// Source of line lengthes for a buffer.
 pub trait LineLength {
@@ -31,3 +45,12 @@ pub trait LineLength {
     /// Returns a total number of lines.
     fn lines_count(&self) -> usize;
 }
+
+/// Source of lines text for a buffer, columns are counted in characters.
+pub trait LineText: LineLength {
+    /// Returns text of a line specified by its index, newline is not
+    /// included.
+    ///
+    /// `None` is returned if requested line is out of buffer's bounds.
+    fn get_line(&self, line: usize) -> Option<Cow<'_, str>>;
+}
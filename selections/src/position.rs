@@ -16,22 +16,22 @@ pub struct Position {
 
 impl PartialOrd for Position {
     fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
-        Some(match self.line.cmp(&other.line) {
-            cmp::Ordering::Equal => self.column.cmp(&other.column),
-            other => other,
-        })
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for Position {
     fn cmp(&self, other: &Self) -> cmp::Ordering {
-        self.partial_cmp(other).expect("total ordering is defined")
+        match self.line.cmp(&other.line) {
+            cmp::Ordering::Equal => self.column.cmp(&other.column),
+            other => other,
+        }
     }
 }
 
 impl Position {
     /// Creates new position with `line` and `column`.
-    pub(crate) fn new(line: usize, column: usize) -> Self {
+    pub fn new(line: usize, column: usize) -> Self {
         Position {
             line,
             column,
@@ -1,5 +1,5 @@
 use super::Position;
-use crate::LineLength;
+use crate::{text::subword_segments, LineLength, LineText};
 
 impl Position {
     pub(crate) fn move_left(&self, line_lengths: &impl LineLength, mut n: usize) -> Position {
@@ -94,4 +94,108 @@ impl Position {
 
         new_pos
     }
+
+    /// Moves to the beginning of the `n`-th next subword, empty lines are
+    /// stops too. Stops on the buffer end if there are no more subwords.
+    pub(crate) fn move_subword_forward(&self, text: &impl LineText, n: usize) -> Position {
+        let mut new_pos = Position::new(self.line, self.column);
+        for _ in 0..n {
+            let next = (new_pos.line..text.lines_count()).find_map(|line| {
+                let segments = subword_segments(&text.get_line(line)?);
+                let after = if line == new_pos.line {
+                    Some(new_pos.column)
+                } else if text.get_len(line) == Some(0) {
+                    return Some(Position::new(line, 0));
+                } else {
+                    None
+                };
+                segments
+                    .iter()
+                    .find(|s| after.is_none_or(|column| s.start > column))
+                    .map(|s| Position::new(line, s.start))
+            });
+            match next {
+                Some(next) => new_pos = next,
+                None => return text_end(text),
+            }
+        }
+        new_pos
+    }
+
+    /// Moves to the beginning of the `n`-th previous subword, empty lines
+    /// are stops too. Stops on the buffer beginning if there are no more
+    /// subwords.
+    pub(crate) fn move_subword_backward(&self, text: &impl LineText, n: usize) -> Position {
+        let mut new_pos = Position::new(self.line, self.column);
+        for _ in 0..n {
+            let previous = (0..=new_pos.line.min(text.lines_count()))
+                .rev()
+                .find_map(|line| {
+                    let segments = subword_segments(&text.get_line(line)?);
+                    let before = if line == new_pos.line {
+                        Some(new_pos.column)
+                    } else if text.get_len(line) == Some(0) {
+                        return Some(Position::new(line, 0));
+                    } else {
+                        None
+                    };
+                    segments
+                        .iter()
+                        .rev()
+                        .find(|s| before.is_none_or(|column| s.start < column))
+                        .map(|s| Position::new(line, s.start))
+                });
+            match previous {
+                Some(previous) => new_pos = previous,
+                None => return Position::new(0, 0),
+            }
+        }
+        new_pos
+    }
+
+    /// Moves to the last character of the `n`-th next subword end. Stops on
+    /// the buffer end if there are no more subwords.
+    pub(crate) fn move_subword_end(&self, text: &impl LineText, n: usize) -> Position {
+        let mut new_pos = Position::new(self.line, self.column);
+        for _ in 0..n {
+            let next = (new_pos.line..text.lines_count()).find_map(|line| {
+                subword_segments(&text.get_line(line)?)
+                    .iter()
+                    .map(|s| Position::new(line, s.end - 1))
+                    .find(|p| p > &new_pos)
+            });
+            match next {
+                Some(next) => new_pos = next,
+                None => return text_end(text),
+            }
+        }
+        new_pos
+    }
+
+    /// Moves to the `n`-th next of sorted sentence `stops` (see
+    /// [BufferText::sentence_stops](crate::text::BufferText::sentence_stops)),
+    /// or to the last one if there are less stops ahead.
+    pub(crate) fn move_sentence_forward(&self, stops: &[Position], n: usize) -> Position {
+        let idx = stops.partition_point(|p| p <= self) + n.saturating_sub(1);
+        match stops.get(idx).or(stops.last()) {
+            Some(stop) if n > 0 => stop.clone(),
+            _ => self.clone().remove_sticky(),
+        }
+    }
+
+    /// Moves to the `n`-th previous of sorted sentence `stops`, or to the
+    /// first one if there are less stops behind.
+    pub(crate) fn move_sentence_backward(&self, stops: &[Position], n: usize) -> Position {
+        let idx = stops.partition_point(|p| p < self).saturating_sub(n);
+        match stops.get(idx) {
+            Some(stop) if n > 0 => stop.clone(),
+            _ => self.clone().remove_sticky(),
+        }
+    }
+}
+
+/// Position of the last line end.
+fn text_end(text: &impl LineText) -> Position {
+    let line = text.lines_count().saturating_sub(1);
+    Position::new(line, text.get_len(line).unwrap_or_default())
 }
@@ -336,3 +336,105 @@ mod move_down {
         assert_eq!(pos, Position::new(3, 5));
     }
 }
+
+mod subword {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::test_utils::TestText;
+
+    fn text() -> TestText {
+        TestText::new("let parseHttpRequest = snake_case;\n\n  x.y")
+    }
+
+    #[test]
+    fn forward() {
+        let text = text();
+        let pos = Position::new(0, 2).move_subword_forward(&text, 1);
+        assert_eq!(pos, Position::new(0, 4));
+
+        let pos = Position::new(0, 5).move_subword_forward(&text, 3);
+        assert_eq!(pos, Position::new(0, 21));
+
+        let pos = Position::new(0, 29).move_subword_forward(&text, 2);
+        assert_eq!(pos, Position::new(1, 0));
+
+        let pos = Position::new(1, 0).move_subword_forward(&text, 1);
+        assert_eq!(pos, Position::new(2, 2));
+
+        let pos = Position::new(2, 3).move_subword_forward(&text, 5);
+        assert_eq!(pos, Position::new(2, 5));
+    }
+
+    #[test]
+    fn backward() {
+        let text = text();
+        let pos = Position::new(0, 20).move_subword_backward(&text, 2);
+        assert_eq!(pos, Position::new(0, 9));
+
+        let pos = Position::new(0, 29).move_subword_backward(&text, 1);
+        assert_eq!(pos, Position::new(0, 23));
+
+        let pos = Position::new(2, 2).move_subword_backward(&text, 2);
+        assert_eq!(pos, Position::new(0, 33));
+
+        let pos = Position::new(0, 2).move_subword_backward(&text, 3);
+        assert_eq!(pos, Position::new(0, 0));
+    }
+
+    #[test]
+    fn end() {
+        let text = text();
+        let pos = Position::new(0, 4).move_subword_end(&text, 1);
+        assert_eq!(pos, Position::new(0, 8));
+
+        let pos = Position::new(0, 8).move_subword_end(&text, 2);
+        assert_eq!(pos, Position::new(0, 19));
+
+        let pos = Position::new(0, 33).move_subword_end(&text, 1);
+        assert_eq!(pos, Position::new(2, 2));
+
+        let pos = Position::new(2, 4).move_subword_end(&text, 1);
+        assert_eq!(pos, Position::new(2, 5));
+    }
+}
+
+mod sentence {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{test_utils::TestText, text::BufferText};
+
+    fn stops() -> Vec<Position> {
+        BufferText::new(&TestText::new("One two. Three\nfour!  Five\n\nSix.")).sentence_stops()
+    }
+
+    #[test]
+    fn forward() {
+        let stops = stops();
+        let pos = Position::new(0, 3).move_sentence_forward(&stops, 1);
+        assert_eq!(pos, Position::new(0, 9));
+
+        let pos = Position::new(0, 3).move_sentence_forward(&stops, 3);
+        assert_eq!(pos, Position::new(2, 0));
+
+        let pos = Position::new(3, 1).move_sentence_forward(&stops, 10);
+        assert_eq!(pos, Position::new(3, 4));
+    }
+
+    #[test]
+    fn backward() {
+        let stops = stops();
+        let pos = Position::new(1, 7).move_sentence_backward(&stops, 1);
+        assert_eq!(pos, Position::new(0, 9));
+
+        let pos = Position::new(1, 8).move_sentence_backward(&stops, 1);
+        assert_eq!(pos, Position::new(1, 7));
+
+        let pos = Position::new(3, 0).move_sentence_backward(&stops, 2);
+        assert_eq!(pos, Position::new(1, 7));
+
+        let pos = Position::new(3, 0).move_sentence_backward(&stops, 10);
+        assert_eq!(pos, Position::new(0, 0));
+    }
+}
@@ -6,14 +6,38 @@ mod test_movement;
 
 use super::Position;
 
-/// Selection is a pair of coordinates in a document.
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Selection {
+/// Selection is a pair of coordinates in a document with a payload attached.
+///
+/// Selections are compared by their coordinates and direction only, neither
+/// payload nor the id assigned by [SelectionStorage](crate::SelectionStorage)
+/// are taken into account.
+#[derive(Debug, Clone)]
+pub struct Selection<T = ()> {
     pub(crate) from: Position,
     pub(crate) to: Position,
     pub(crate) direction: SelectionDirection,
+    pub(crate) id: Option<SelectionId>,
+    pub(crate) payload: T,
 }
 
+/// Stable id is left out, as it's bookkeeping of a storage rather than a
+/// state: deltas compare old and new states of the same selection, and a
+/// selection built by hand (having no id yet) should be equal to the stored
+/// one with the same coordinates.
+impl<T> PartialEq for Selection<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.from == other.from && self.to == other.to && self.direction == other.direction
+    }
+}
+
+impl<T> Eq for Selection<T> {}
+
+/// Stable identifier of a selection assigned by
+/// [SelectionStorage](crate::SelectionStorage) on creation, it doesn't change
+/// when the selection is moved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SelectionId(pub(crate) u64);
+
 /// Direction of a selection.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SelectionDirection {
@@ -23,12 +47,14 @@ pub enum SelectionDirection {
     Backward,
 }
 
-impl Default for Selection {
+impl<T: Default> Default for Selection<T> {
     fn default() -> Self {
         Selection {
             from: Position::new(0, 0),
             to: Position::new(0, 0),
             direction: SelectionDirection::Forward,
+            id: None,
+            payload: T::default(),
         }
     }
 }
@@ -37,17 +63,29 @@ impl Selection {
     /// Build new selection from two positions setting proper direction
     /// depending on order.
     pub fn new(anchor: Position, cursor: Position) -> Self {
+        Selection::with_payload(anchor, cursor, ())
+    }
+}
+
+impl<T> Selection<T> {
+    /// Build new selection from two positions setting proper direction
+    /// depending on order, with `payload` attached.
+    pub fn with_payload(anchor: Position, cursor: Position, payload: T) -> Self {
         if anchor > cursor {
             Selection {
                 from: cursor,
                 to: anchor,
                 direction: SelectionDirection::Backward,
+                id: None,
+                payload,
             }
         } else {
             Selection {
                 from: anchor,
                 to: cursor,
                 direction: SelectionDirection::Forward,
+                id: None,
+                payload,
             }
         }
     }
@@ -75,4 +113,22 @@ impl Selection {
     pub fn id(&self) -> &Position {
         &self.from
     }
+
+    /// Get selection's stable id, it's `None` unless the selection is stored
+    /// in [SelectionStorage](crate::SelectionStorage).
+    #[inline]
+    pub fn selection_id(&self) -> Option<SelectionId> {
+        self.id
+    }
+
+    /// Get data attached to the selection.
+    #[inline]
+    pub fn payload(&self) -> &T {
+        &self.payload
+    }
+
+    /// Drops selection's coordinates returning its payload.
+    pub fn into_payload(self) -> T {
+        self.payload
+    }
 }
@@ -1,15 +1,18 @@
 //! Movement implementations for a single selection.
 
-use crate::{LineLength, Position, Selection};
+use crate::{LineLength, LineText, Position, Selection};
 
-impl Selection {
-    fn move_generic(&self, extend: bool, move_fn: impl Fn(&Position) -> Position) -> Selection {
+impl<T: Clone> Selection<T> {
+    fn move_generic(&self, extend: bool, move_fn: impl Fn(&Position) -> Position) -> Selection<T> {
         let cursor = move_fn(self.cursor());
-        if extend {
-            Selection::new(self.anchor().clone(), cursor)
+        let payload = self.payload.clone();
+        let mut selection = if extend {
+            Selection::with_payload(self.anchor().clone(), cursor, payload)
         } else {
-            Selection::new(cursor.clone().remove_sticky(), cursor)
-        }
+            Selection::with_payload(cursor.clone().remove_sticky(), cursor, payload)
+        };
+        selection.id = self.id;
+        selection
     }
 
     pub(crate) fn move_left(
@@ -17,7 +20,7 @@ impl Selection {
         line_lengths: &impl LineLength,
         n: usize,
         extend: bool,
-    ) -> Selection {
+    ) -> Selection<T> {
         self.move_generic(extend, |p| p.move_left(line_lengths, n))
     }
 
@@ -26,7 +29,7 @@ impl Selection {
         line_lengths: &impl LineLength,
         n: usize,
         extend: bool,
-    ) -> Selection {
+    ) -> Selection<T> {
         self.move_generic(extend, |p| p.move_right(line_lengths, n))
     }
 
@@ -35,7 +38,7 @@ impl Selection {
         line_lengths: &impl LineLength,
         n: usize,
         extend: bool,
-    ) -> Selection {
+    ) -> Selection<T> {
         self.move_generic(extend, |p| p.move_up(line_lengths, n))
     }
 
@@ -44,7 +47,52 @@ impl Selection {
         line_lengths: &impl LineLength,
         n: usize,
         extend: bool,
-    ) -> Selection {
+    ) -> Selection<T> {
         self.move_generic(extend, |p| p.move_down(line_lengths, n))
     }
+
+    pub(crate) fn move_subword_forward(
+        &self,
+        text: &impl LineText,
+        n: usize,
+        extend: bool,
+    ) -> Selection<T> {
+        self.move_generic(extend, |p| p.move_subword_forward(text, n))
+    }
+
+    pub(crate) fn move_subword_backward(
+        &self,
+        text: &impl LineText,
+        n: usize,
+        extend: bool,
+    ) -> Selection<T> {
+        self.move_generic(extend, |p| p.move_subword_backward(text, n))
+    }
+
+    pub(crate) fn move_subword_end(
+        &self,
+        text: &impl LineText,
+        n: usize,
+        extend: bool,
+    ) -> Selection<T> {
+        self.move_generic(extend, |p| p.move_subword_end(text, n))
+    }
+
+    pub(crate) fn move_sentence_forward(
+        &self,
+        stops: &[Position],
+        n: usize,
+        extend: bool,
+    ) -> Selection<T> {
+        self.move_generic(extend, |p| p.move_sentence_forward(stops, n))
+    }
+
+    pub(crate) fn move_sentence_backward(
+        &self,
+        stops: &[Position],
+        n: usize,
+        extend: bool,
+    ) -> Selection<T> {
+        self.move_generic(extend, |p| p.move_sentence_backward(stops, n))
+    }
 }
@@ -1,50 +1,141 @@
 //! [SelectionStorage] declaration and implementation.
 
+mod apply;
+mod bulk;
+mod clipboard;
+mod collision;
+mod expand;
 mod get;
 mod insert;
+mod key;
+mod map;
+mod matches;
 mod movement;
+mod objects;
+mod observers;
+mod occurrence;
+mod payload;
+mod primary;
 mod query;
+mod spans;
+mod split;
+mod syntax;
 #[cfg(test)]
 mod test_movement;
+mod track;
+mod transaction;
+
+use std::collections::HashMap;
+
+pub use clipboard::{PasteMode, TextEdit};
+pub use collision::{CollisionConfig, CollisionPolicy};
+use expand::Expansion;
+pub use key::SelectionKey;
+pub use matches::Matcher;
+pub use observers::ObserverId;
+use observers::Observers;
+use payload::PayloadMerge;
+pub use spans::{LineCaret, LineHighlights, LineSpan, LineSpans};
+pub use syntax::{SyntaxNodeTarget, SyntaxTree};
+use transaction::Transaction;
 
 use super::*;
+use crate::deltas::CloneSelection;
 
-/// Multiselection storage.
-pub struct SelectionStorage {
+/// Multiselection storage, each selection could carry a payload of type `T`.
+pub struct SelectionStorage<T = ()> {
     /// Selections sorted by `from` position in the buffer.
-    selections: Vec<Selection>,
-    // TODO: active
+    selections: Vec<Selection<T>>,
+    /// Collision resolution settings.
+    collisions: CollisionConfig,
+    /// Id of the primary selection.
+    primary: Option<SelectionId>,
+    /// Transactions in progress, the last one is the innermost.
+    transactions: Vec<Transaction<T>>,
+    /// Callbacks to notify about changes.
+    observers: Observers<T>,
+    /// Whether deltas are annotated with storage indices.
+    delta_indices: bool,
+    /// `from` positions of selections by their ids.
+    ids: HashMap<SelectionId, Position>,
+    /// Id to assign to the next created selection.
+    next_id: u64,
+    /// Hook to merge payload of an absorbed selection into the absorber's one.
+    payload_merge: Option<PayloadMerge<T>>,
+    /// Expansion history of selections by their ids.
+    expansions: HashMap<SelectionId, Expansion>,
+    /// Clones selections to detach deltas for observers and transactions, set
+    /// once they're used as only they require `T: Clone`.
+    clone_selection: Option<CloneSelection<T>>,
 }
 
 impl SelectionStorage {
     /// Create selection storage with an initial selection in the buffer
     /// beginning.
     pub fn new() -> Self {
-        SelectionStorage {
-            selections: vec![Selection::default()],
-        }
+        Self::with_payload(())
     }
 
     #[cfg(test)]
     /// Create selection storage with no default selection.
     fn new_empty() -> Self {
+        Self::empty()
+    }
+}
+
+impl<T> SelectionStorage<T> {
+    /// Create selection storage with an initial selection in the buffer
+    /// beginning which carries `payload`.
+    pub fn with_payload(payload: T) -> Self {
+        let mut storage = Self::empty();
+        let selection = storage.identify(Selection::with_payload(
+            Position::new(0, 0),
+            Position::new(0, 0),
+            payload,
+        ));
+        storage.primary = selection.id;
+        storage.selections.push(selection);
+        storage.rebuild_ids();
+        storage
+    }
+
+    /// Create selection storage with no selections.
+    fn empty() -> Self {
         SelectionStorage {
             selections: Vec::new(),
+            collisions: Default::default(),
+            primary: None,
+            transactions: Vec::new(),
+            observers: Default::default(),
+            delta_indices: false,
+            ids: HashMap::new(),
+            next_id: 0,
+            payload_merge: None,
+            expansions: HashMap::new(),
+            clone_selection: None,
         }
     }
 
     #[cfg(debug_assertions)]
-    /// Verify if selections are kept sorted and with no overlaps.
+    /// Verify if selections are kept sorted and with no overlaps, and ids
+    /// lookup table is in sync.
     fn is_state_correct(&self) -> bool {
-        self.selections
-            .as_slice()
-            .windows(2)
-            .all(|w| w[0].to < w[1].from)
+        let ids_correct = self.ids.len() == self.selections.len()
+            && self.selections.iter().all(|s| {
+                s.id.and_then(|id| self.ids.get(&id))
+                    .is_some_and(|from| from.weak_eq(&s.from))
+            });
+        ids_correct
+            && self
+                .selections
+                .as_slice()
+                .windows(2)
+                .all(|w| w[0].to < w[1].from)
     }
 }
 
-impl Default for SelectionStorage {
+impl<T: Default> Default for SelectionStorage<T> {
     fn default() -> Self {
-        Self::new()
+        Self::with_payload(T::default())
     }
 }
@@ -0,0 +1,261 @@
+//! Application of previously received deltas to [SelectionStorage].
+
+use std::{collections::HashMap, mem};
+
+use super::{bulk::BulkStep, query::SelectionIndex, SelectionKey, SelectionStorage};
+use crate::{OwnedSelectionDelta, OwnedSelectionDeltas, Selection, SelectionDeltas, SelectionId};
+
+impl<T: Clone> SelectionStorage<T> {
+    /// Applies `deltas` to the storage: selections of `Deleted` and old states
+    /// of `Updated` are removed, selections of `Created` and new states of
+    /// `Updated` are inserted. Combined with [OwnedSelectionDeltas::invert]
+    /// it reverts changes.
+    ///
+    /// Deltas are expected to be received from this storage and to be
+    /// consistent with its current state: deltas referring to missing
+    /// selections are skipped and inserted selections overlapping
+    /// others are merged.
+    pub fn apply_deltas(&mut self, deltas: &OwnedSelectionDeltas<T>) -> SelectionDeltas<'_, T> {
+        self.tracked(|storage| storage.apply_deltas_internal(deltas))
+    }
+
+    /// Common logic of deltas application.
+    fn apply_deltas_internal(
+        &mut self,
+        deltas: &OwnedSelectionDeltas<T>,
+    ) -> SelectionDeltas<'_, T> {
+        let primary = self.primary;
+
+        let mut removed_indices: Vec<SelectionIndex> = deltas
+            .iter()
+            .filter_map(|delta| match delta {
+                OwnedSelectionDelta::Deleted(s) | OwnedSelectionDelta::Updated { old: s, .. } => {
+                    // Selections without ids could be found by their position only:
+                    let key =
+                        s.id.map_or(SelectionKey::Position(&s.from), SelectionKey::Id);
+                    self.find_index_by_key(key)
+                }
+                OwnedSelectionDelta::Created(_) => None,
+            })
+            .collect();
+        removed_indices.sort_unstable();
+        removed_indices.dedup();
+
+        // Selections restored from deltas keep their ids:
+        let batch: Vec<Selection<T>> = deltas
+            .iter()
+            .filter_map(|delta| match delta {
+                OwnedSelectionDelta::Created(s) | OwnedSelectionDelta::Updated { new: s, .. } => {
+                    Some(s.clone())
+                }
+                OwnedSelectionDelta::Deleted(_) => None,
+            })
+            .map(|s| match s.id {
+                Some(_) => s,
+                None => self.identify(s),
+            })
+            .collect();
+
+        // Removed selections are split off in one pass, then inserted ones are merged
+        // with the rest in another one:
+        let mut removed_indices = removed_indices.into_iter().peekable();
+        let mut removed = Vec::with_capacity(removed_indices.len());
+        let mut kept = Vec::with_capacity(self.selections.len());
+        for (idx, s) in mem::take(&mut self.selections).into_iter().enumerate() {
+            if removed_indices.next_if_eq(&idx).is_some() {
+                removed.push(Some((idx, s)));
+            } else {
+                kept.push((idx, s));
+            }
+        }
+        let (steps, last_idx) = self.merge_batch(kept.into_iter(), batch);
+
+        // Insertion changes primary selection, but it's not what the delta is about:
+        self.primary = primary;
+        if let Some(idx) = last_idx {
+            self.fix_primary(idx);
+        }
+        debug_assert!(self.is_state_correct());
+
+        // A removed selection inserted again with the same id is updated:
+        let removed_by_id: HashMap<SelectionId, usize> = removed
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, removed)| Some((removed.as_ref()?.1.id?, idx)))
+            .collect();
+        let mut result = SelectionDeltas::with_capacity(removed.len() + steps.len());
+        for step in steps {
+            match step {
+                BulkStep::Merged(old, old_idx, idx) => {
+                    result.push_merged(old, old_idx, self.selections[idx].id)
+                }
+                BulkStep::Created(idx) => {
+                    let new = &self.selections[idx];
+                    match new
+                        .id
+                        .and_then(|id| removed_by_id.get(&id))
+                        .and_then(|removed_idx| removed[*removed_idx].take())
+                    {
+                        Some((old_idx, old)) => result.push_updated(old, old_idx, new, idx),
+                        None => result.push_created(new, idx),
+                    }
+                }
+            }
+        }
+        for (old_idx, s) in removed.into_iter().flatten() {
+            result.push_deleted(s, old_idx);
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_utils::{selections_test, TestLineLengths},
+        Position, Selection,
+    };
+
+    #[test]
+    fn revert_insert() {
+        selections_test! {
+            [
+                (1, 3) - (3, 7),
+                (4, 3) - (5, 7),
+            ],
+            storage -> {
+                let deltas = storage
+                    .insert(Selection::new(Position::new(3, 5), Position::new(4, 7)))
+                    .into_owned();
+                storage.apply_deltas(&deltas.invert())
+            },
+            [
+                Deleted((1, 3) - (5, 7)),
+                Created((1, 3) - (3, 7)),
+                Created((4, 3) - (5, 7)),
+            ],
+            [
+                (1, 3) - (3, 7),
+                (4, 3) - (5, 7),
+            ]
+        };
+    }
+
+    #[test]
+    fn revert_movement() {
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+                (0, 5) - (0, 8),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 20);
+                let deltas = storage
+                    .move_right_single(&line_lengths, &Position::new(0, 0), 4, true)
+                    .into_owned();
+                storage.apply_deltas(&deltas.invert())
+            },
+            [
+                Updated {
+                    old: (0, 0) - (0, 8),
+                    new: (0, 0) - (0, 2),
+                },
+                Created((0, 5) - (0, 8)),
+            ],
+            [
+                (0, 0) - (0, 2),
+                (0, 5) - (0, 8),
+            ]
+        };
+    }
+
+    #[test]
+    fn reapply() {
+        selections_test! {
+            [
+                (0, 0) - (0, 0) sticky 10,
+                (1, 8) - (1, 12),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 20);
+                line_lengths.set(1, 20);
+                let deltas = storage
+                    .move_down_single(&line_lengths, &Position::new(0, 0), 1, false)
+                    .into_owned();
+                storage.apply_deltas(&deltas.clone().invert());
+                storage.apply_deltas(&deltas)
+            },
+            [
+                Updated {
+                    old: (0, 0) - (0, 0) sticky 10,
+                    new: (1, 10) - (1, 10),
+                },
+                Deleted((1, 8) - (1, 12)),
+            ],
+            [
+                (1, 10) - (1, 10),
+            ]
+        };
+    }
+
+    #[test]
+    fn reinsert_merged() {
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 20);
+                let deltas = storage
+                    .insert(Selection::new(Position::new(0, 4), Position::new(0, 6)))
+                    .into_owned();
+                storage.apply_deltas(&deltas.clone().invert());
+                storage.move_right_single(&line_lengths, &Position::new(0, 0), 3, true);
+                storage.apply_deltas(&deltas)
+            },
+            [
+                Deleted((0, 0) - (0, 5)),
+                Created((0, 0) - (0, 6)),
+            ],
+            [
+                (0, 0) - (0, 6),
+            ]
+        };
+    }
+
+    #[test]
+    fn delete_by_id() {
+        selections_test! {
+            [
+                (0, 0) - (0, 1),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(2, 10);
+                let deltas = storage
+                    .insert(Selection::new(Position::new(2, 0), Position::new(2, 1)))
+                    .into_owned();
+                storage.move_with_config(
+                    &line_lengths,
+                    &Position::new(2, 0),
+                    false,
+                    Default::default(),
+                    |_| Selection::new(Position::new(2, 5), Position::new(2, 6)),
+                );
+                storage.insert(Selection::new(Position::new(2, 0), Position::new(2, 1)));
+                storage.apply_deltas(&deltas.invert())
+            },
+            [
+                Deleted((2, 5) - (2, 6)),
+            ],
+            [
+                (0, 0) - (0, 1),
+                (2, 0) - (2, 1),
+            ]
+        };
+    }
+}
@@ -0,0 +1,230 @@
+//! Bulk construction and extension of [SelectionStorage].
+
+use std::mem;
+
+use super::{query::SelectionIndex, SelectionStorage};
+use crate::{Selection, SelectionDeltas};
+
+/// Change made to stored selections by a bulk extension.
+pub(super) enum BulkStep<T> {
+    /// Existing selection from the old index is absorbed by a selection on the
+    /// new index.
+    Merged(Selection<T>, SelectionIndex, SelectionIndex),
+    /// Selection on the index is created.
+    Created(SelectionIndex),
+}
+
+impl<T> SelectionStorage<T> {
+    /// Create selection storage from `selections` in any order, overlapping
+    /// ones are merged. The last one becomes primary.
+    pub fn from_selections(selections: impl IntoIterator<Item = Selection<T>>) -> Self {
+        let mut storage = Self::empty();
+        storage.extend_from(selections);
+        storage
+    }
+
+    /// Inserts all `selections` at once, just like [Self::insert] does one by
+    /// one, but in `O((n + m) log m)`: selections are sorted once (unless
+    /// they're already sorted) and merged with stored ones in a single pass.
+    /// The last one becomes primary.
+    pub fn extend_from(
+        &mut self,
+        selections: impl IntoIterator<Item = Selection<T>>,
+    ) -> SelectionDeltas<'_, T> {
+        self.tracked(|storage| storage.extend_from_internal(selections))
+    }
+
+    fn extend_from_internal(
+        &mut self,
+        selections: impl IntoIterator<Item = Selection<T>>,
+    ) -> SelectionDeltas<'_, T> {
+        let batch: Vec<Selection<T>> = selections.into_iter().map(|s| self.identify(s)).collect();
+        if batch.is_empty() {
+            return SelectionDeltas::new();
+        }
+
+        let old_selections = mem::take(&mut self.selections).into_iter().enumerate();
+        let (steps, primary_idx) = self.merge_batch(old_selections, batch);
+        if let Some(idx) = primary_idx {
+            self.set_primary_index(idx);
+        }
+
+        let mut deltas = SelectionDeltas::with_capacity(steps.len());
+        for step in steps {
+            match step {
+                BulkStep::Merged(old, old_idx, idx) => {
+                    deltas.push_merged(old, old_idx, self.selections[idx].id)
+                }
+                BulkStep::Created(idx) => deltas.push_created(&self.selections[idx], idx),
+            }
+        }
+
+        debug_assert!(self.is_state_correct());
+        deltas
+    }
+
+    /// Merges sorted `old_selections` (with their indices before the change)
+    /// and `batch` of new selections in any order into the storage, which is
+    /// expected to be empty. Overlapping selections are merged just like
+    /// [Self::insert] does. Returns changes made and the new index of the last
+    /// selection of the batch.
+    pub(super) fn merge_batch(
+        &mut self,
+        old_selections: impl Iterator<Item = (SelectionIndex, Selection<T>)>,
+        batch: Vec<Selection<T>>,
+    ) -> (Vec<BulkStep<T>>, Option<SelectionIndex>) {
+        let last_input = batch.len().checked_sub(1);
+        let mut batch: Vec<(usize, Selection<T>)> = batch.into_iter().enumerate().collect();
+        // Just like `UniqueSortedVec::push`, sorted input costs nothing extra. The sort
+        // is stable, so on `from` collision input order is kept:
+        if !batch.is_sorted_by(|(_, a), (_, b)| a.from <= b.from) {
+            batch.sort_by(|(_, a), (_, b)| a.from.cmp(&b.from));
+        }
+
+        let mut old_selections = old_selections.peekable();
+        let mut batch = batch.into_iter().peekable();
+        self.selections
+            .reserve(old_selections.size_hint().0 + batch.len());
+        let mut steps = Vec::new();
+        let mut primary_idx = None;
+        // Old index of the last stored selection, unless it comes from the batch:
+        let mut last_old_idx = None;
+        loop {
+            // Both lists are sorted, so the next selection is the leftmost of their
+            // heads, existing one goes first on a tie:
+            let take_old = match (old_selections.peek(), batch.peek()) {
+                (Some((_, old)), Some((_, new))) => old.from <= new.from,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+            let (old_idx, input_idx, selection) = if take_old {
+                let (idx, selection) = old_selections.next().expect("peeked");
+                (Some(idx), None, selection)
+            } else {
+                let (idx, selection) = batch.next().expect("peeked");
+                (None, Some(idx), selection)
+            };
+
+            let last_idx = self.selections.len().wrapping_sub(1);
+            match (self.selections.last_mut(), last_old_idx) {
+                // Existing selection is absorbed by a new one, so the new one takes its place:
+                (Some(last), Some(absorbed_idx)) if last.to >= selection.from => {
+                    let mut selection = selection;
+                    selection.from = last.from.clone();
+                    if last.to > selection.to {
+                        selection.to = last.to.clone();
+                    }
+                    let old = mem::replace(last, selection);
+                    self.merge_payload(last_idx, &old);
+                    steps.push(BulkStep::Merged(old, absorbed_idx, last_idx));
+                    steps.push(BulkStep::Created(last_idx));
+                    last_old_idx = None;
+                }
+                // New selection absorbs the next one, either existing or new:
+                (Some(last), None) if last.to >= selection.from => {
+                    if selection.to > last.to {
+                        last.to = selection.to.clone();
+                    }
+                    self.merge_payload(last_idx, &selection);
+                    if let Some(old_idx) = old_idx {
+                        steps.push(BulkStep::Merged(selection, old_idx, last_idx));
+                    }
+                }
+                _ => {
+                    self.selections.push(selection);
+                    last_old_idx = old_idx;
+                    if input_idx.is_some() {
+                        steps.push(BulkStep::Created(self.selections.len() - 1));
+                    }
+                }
+            }
+            if input_idx.is_some() && input_idx == last_input {
+                primary_idx = Some(self.selections.len() - 1);
+            }
+        }
+
+        self.rebuild_ids();
+        (steps, primary_idx)
+    }
+}
+
+impl<T> FromIterator<Selection<T>> for SelectionStorage<T> {
+    fn from_iter<I: IntoIterator<Item = Selection<T>>>(iter: I) -> Self {
+        Self::from_selections(iter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::test_utils::{selection, selections_test};
+
+    #[test]
+    fn from_unsorted_selections() {
+        let storage: SelectionStorage = [
+            selection((2, 0), (2, 3)),
+            selection((0, 5), (0, 1)),
+            selection((2, 2), (3, 0)),
+            selection((1, 0), (1, 0)),
+            selection((0, 0), (0, 2)),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            storage.iter_all().cloned().collect::<Vec<_>>(),
+            [
+                selection((0, 0), (0, 5)),
+                selection((1, 0), (1, 0)),
+                selection((2, 0), (3, 0)),
+            ]
+        );
+        assert_eq!(storage.primary(), Some(&selection((0, 0), (0, 5))));
+    }
+
+    #[test]
+    fn from_no_selections() {
+        let storage = SelectionStorage::<()>::from_selections([]);
+        assert_eq!(storage.iter_all().count(), 0);
+        assert_eq!(storage.primary(), None);
+    }
+
+    #[test]
+    fn extend_from() {
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+                (1, 0) - (1, 5),
+                (2, 0) - (2, 1),
+                (3, 4) - (3, 6),
+            ],
+            storage -> {
+                storage.extend_from([
+                    selection((4, 0), (4, 1)),
+                    selection((1, 3), (2, 0)),
+                    selection((0, 4), (0, 4)),
+                    selection((3, 0), (3, 4)),
+                ])
+            },
+            [
+                Created((0, 4) - (0, 4)),
+                Deleted((1, 0) - (1, 5)),
+                Created((1, 0) - (2, 1)),
+                Deleted((2, 0) - (2, 1)),
+                Created((3, 0) - (3, 6)),
+                Deleted((3, 4) - (3, 6)),
+                Created((4, 0) - (4, 1)),
+            ],
+            [
+                (0, 0) - (0, 2),
+                (0, 4) - (0, 4),
+                (1, 0) - (2, 1),
+                (3, 0) - (3, 6),
+                (4, 0) - (4, 1),
+            ]
+        };
+    }
+}
@@ -0,0 +1,352 @@
+//! Per-selection clipboard of [SelectionStorage]: yank and paste.
+
+use super::{spans::line_span, CollisionConfig, SelectionStorage};
+use crate::{LineLength, LineText, Position, Selection, SelectionDeltas, SelectionDirection};
+
+/// Where pasted text goes relative to a selection, see
+/// [SelectionStorage::paste].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasteMode {
+    /// Text is inserted before the selection.
+    Before,
+    /// Text is inserted after the selection.
+    After,
+    /// Text replaces the selected one.
+    Replace,
+}
+
+/// Change of buffer text: text from `start` up to `end` (exclusive) is
+/// replaced with `text`. Insertions have `start` equal to `end`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    /// Beginning of the replaced text.
+    pub start: Position,
+    /// Position after the end of the replaced text.
+    pub end: Position,
+    /// Text to put instead.
+    pub text: String,
+}
+
+impl<T> SelectionStorage<T> {
+    /// Returns text of each selection in storage order. Selection covering a
+    /// line end gets a newline.
+    pub fn yank(&self, text: &impl LineText) -> Vec<String> {
+        self.selections
+            .iter()
+            .map(|s| selection_text(text, s))
+            .collect()
+    }
+}
+
+impl<T: Clone> SelectionStorage<T> {
+    /// Plans a paste of clipboard `entries`: entry `i` goes to selection `i`
+    /// if numbers match, a single entry is repeated for all selections,
+    /// otherwise all entries joined with newlines go to each selection. An
+    /// empty clipboard changes nothing.
+    ///
+    /// Returns edits in buffer order with positions before the paste, so
+    /// they should be applied starting from the last one. Selections are
+    /// updated to cover pasted text after the edits are applied.
+    pub fn paste<S: AsRef<str>>(
+        &mut self,
+        line_lengths: &impl LineLength,
+        entries: &[S],
+        mode: PasteMode,
+    ) -> (Vec<TextEdit>, SelectionDeltas<'_, T>) {
+        if entries.is_empty() {
+            return (Vec::new(), SelectionDeltas::new());
+        }
+
+        let n = self.selections.len();
+        let joined;
+        let texts: Vec<&str> = if entries.len() == n {
+            entries.iter().map(AsRef::as_ref).collect()
+        } else if let [entry] = entries {
+            vec![entry.as_ref(); n]
+        } else {
+            joined = entries
+                .iter()
+                .map(AsRef::as_ref)
+                .collect::<Vec<_>>()
+                .join("\n");
+            vec![joined.as_str(); n]
+        };
+
+        let mut edits = Vec::with_capacity(n);
+        let mut new_states = Vec::with_capacity(n);
+        let mut shift = Shift::default();
+        for (selection, text) in self.selections.iter().zip(texts) {
+            let after = position_after(line_lengths, &selection.to);
+            let (start, end) = match mode {
+                PasteMode::Before => (selection.from.clone(), selection.from.clone()),
+                PasteMode::After => (after.clone(), after),
+                PasteMode::Replace => (selection.from.clone(), after),
+            };
+
+            let new_start = shift.apply(&start);
+            let (new_end, last) = text_end(&new_start, text);
+            shift = Shift {
+                lines: new_end.line as isize - end.line as isize,
+                line: end.line,
+                columns: new_end.column as isize - end.column as isize,
+            };
+
+            new_states.push(Selection {
+                to: last.unwrap_or_else(|| new_start.clone()),
+                from: new_start,
+                direction: SelectionDirection::Forward,
+                id: selection.id,
+                payload: selection.payload.clone(),
+            });
+            edits.push(TextEdit {
+                start,
+                end,
+                text: text.to_owned(),
+            });
+        }
+
+        // Pasted texts don't overlap and line lengths after the paste are unknown, so
+        // selections touching each other are left as is:
+        let mut new_states = new_states.into_iter();
+        let config = CollisionConfig::default();
+        let deltas = self.map_all_with_config(line_lengths, config, move |_| {
+            new_states.next().expect("a new state for each selection")
+        });
+        (edits, deltas)
+    }
+}
+
+/// Text covered by `selection`.
+pub(super) fn selection_text<T>(text: &impl LineText, selection: &Selection<T>) -> String {
+    let mut result = String::new();
+    for line in selection.from.line..=selection.to.line {
+        let Some(content) = text.get_line(line) else {
+            break;
+        };
+        let span = line_span(selection, line, content.chars().count(), false);
+        result.extend(content.chars().skip(span.start).take(span.end - span.start));
+        if span.newline {
+            result.push('\n');
+        }
+    }
+    result
+}
+
+/// Position right after `position`, which is the next line beginning for a
+/// line end. There is nothing after the last line end, so it stays there.
+fn position_after(line_lengths: &impl LineLength, position: &Position) -> Position {
+    match line_lengths.get_len(position.line) {
+        Some(len) if position.column < len => Position::new(position.line, position.column + 1),
+        Some(len) if line_lengths.get_len(position.line + 1).is_none() => {
+            Position::new(position.line, len)
+        }
+        _ => Position::new(position.line + 1, 0),
+    }
+}
+
+/// Position after `text` inserted at `start`, and position of its last
+/// character if any.
+fn text_end(start: &Position, text: &str) -> (Position, Option<Position>) {
+    let (mut line, mut column) = (start.line, start.column);
+    let mut last = None;
+    for ch in text.chars() {
+        last = Some(Position::new(line, column));
+        if ch == '\n' {
+            line += 1;
+            column = 0;
+        } else {
+            column += 1;
+        }
+    }
+    (Position::new(line, column), last)
+}
+
+/// Translation of positions located after the last applied edit.
+#[derive(Default)]
+struct Shift {
+    /// Lines added (or removed if negative) by edits so far.
+    lines: isize,
+    /// Line where the last edit ended.
+    line: usize,
+    /// Columns added on the `line` after the last edit.
+    columns: isize,
+}
+
+impl Shift {
+    fn apply(&self, position: &Position) -> Position {
+        let column = if position.line == self.line {
+            (position.column as isize + self.columns) as usize
+        } else {
+            position.column
+        };
+        Position::new((position.line as isize + self.lines) as usize, column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::test_utils::{selections_test, TestText};
+
+    fn edit(start: (usize, usize), end: (usize, usize), text: &str) -> TextEdit {
+        TextEdit {
+            start: Position::new(start.0, start.1),
+            end: Position::new(end.0, end.1),
+            text: text.to_owned(),
+        }
+    }
+
+    #[test]
+    fn yank() {
+        let text = TestText::new("hello world\nfoo\nbar baz");
+        let mut storage = SelectionStorage::new();
+        storage.insert(Selection::new(Position::new(0, 6), Position::new(0, 11)));
+        storage.insert(Selection::new(Position::new(1, 2), Position::new(2, 2)));
+
+        assert_eq!(storage.yank(&text), ["h", "world\n", "o\nbar"]);
+    }
+
+    #[test]
+    fn paste_distributed() {
+        let text = TestText::new("ab cd ef");
+        selections_test! {
+            [
+                (0, 0) - (0, 1),
+                (0, 3) - (0, 4),
+            ],
+            storage -> {
+                let (edits, deltas) = storage.paste(&text, &["X", "YY"], PasteMode::Replace);
+                assert_eq!(edits, [edit((0, 0), (0, 2), "X"), edit((0, 3), (0, 5), "YY")]);
+                deltas
+            },
+            [
+                Updated {
+                    old: (0, 0) - (0, 1),
+                    new: (0, 0) - (0, 0),
+                },
+                Updated {
+                    old: (0, 3) - (0, 4),
+                    new: (0, 2) - (0, 3),
+                },
+            ],
+            [
+                (0, 0) - (0, 0),
+                (0, 2) - (0, 3),
+            ]
+        };
+    }
+
+    #[test]
+    fn paste_repeated_multiline() {
+        let text = TestText::new("ab cd\nef");
+        selections_test! {
+            [
+                (0, 1) - (0, 1),
+                (0, 3) - (0, 3),
+                (1, 0) - (1, 0),
+            ],
+            storage -> {
+                let (edits, deltas) = storage.paste(&text, &["1\n2"], PasteMode::After);
+                assert_eq!(
+                    edits,
+                    [
+                        edit((0, 2), (0, 2), "1\n2"),
+                        edit((0, 4), (0, 4), "1\n2"),
+                        edit((1, 1), (1, 1), "1\n2"),
+                    ]
+                );
+                deltas
+            },
+            [
+                Updated {
+                    old: (0, 1) - (0, 1),
+                    new: (0, 2) - (1, 0),
+                },
+                Updated {
+                    old: (0, 3) - (0, 3),
+                    new: (1, 3) - (2, 0),
+                },
+                Updated {
+                    old: (1, 0) - (1, 0),
+                    new: (3, 1) - (4, 0),
+                },
+            ],
+            [
+                (0, 2) - (1, 0),
+                (1, 3) - (2, 0),
+                (3, 1) - (4, 0),
+            ]
+        };
+    }
+
+    #[test]
+    fn paste_joined_before() {
+        let text = TestText::new("abc\ndef");
+        selections_test! {
+            [
+                (0, 1) - (0, 3),
+                (1, 1) - (1, 1),
+            ],
+            storage -> {
+                let (edits, deltas) = storage.paste(&text, &["x", "y", "z"], PasteMode::Before);
+                assert_eq!(
+                    edits,
+                    [edit((0, 1), (0, 1), "x\ny\nz"), edit((1, 1), (1, 1), "x\ny\nz")]
+                );
+                deltas
+            },
+            [
+                Updated {
+                    old: (0, 1) - (0, 3),
+                    new: (0, 1) - (2, 0),
+                },
+                Updated {
+                    old: (1, 1) - (1, 1),
+                    new: (3, 1) - (5, 0),
+                },
+            ],
+            [
+                (0, 1) - (2, 0),
+                (3, 1) - (5, 0),
+            ]
+        };
+    }
+
+    #[test]
+    fn paste_nothing() {
+        let text = TestText::new("ab cd");
+        let mut storage = SelectionStorage::new();
+        let (edits, deltas) = storage.paste(&text, &[] as &[&str], PasteMode::Replace);
+        assert!(edits.is_empty());
+        assert_eq!(deltas.iter().count(), 0);
+    }
+
+    #[test]
+    fn paste_after_last_line_end() {
+        let text = TestText::new(
+            "ab
+cd",
+        );
+        selections_test! {
+            [
+                (1, 1) - (1, 2),
+            ],
+            storage -> {
+                let (edits, deltas) = storage.paste(&text, &["X"], PasteMode::After);
+                assert_eq!(edits, [edit((1, 2), (1, 2), "X")]);
+                deltas
+            },
+            [
+                Updated {
+                    old: (1, 1) - (1, 2),
+                    new: (1, 2) - (1, 2),
+                },
+            ],
+            [
+                (1, 2) - (1, 2),
+            ]
+        };
+    }
+}
@@ -0,0 +1,112 @@
+//! Collision resolution settings of [SelectionStorage].
+
+use super::{
+    query::{SelectionIndex, SelectionIndexRange},
+    SelectionStorage,
+};
+use crate::{LineLength, Position, Selection};
+
+/// Policy describing what happens when a new selection state (inserted or
+/// moved one) overlaps with existing selections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CollisionPolicy {
+    /// New selection absorbs overlapped ones.
+    #[default]
+    Merge,
+    /// New selection replaces overlapped ones as is.
+    Replace,
+    /// Operation is cancelled, storage remains unchanged.
+    RejectNew,
+    /// Existing selections remain, inserted or moved selection is dropped.
+    KeepExisting,
+    /// New selection is trimmed to fit a gap between existing selections where
+    /// its cursor is. If the cursor itself is inside of an existing
+    /// selection the operation is cancelled.
+    TrimToFit,
+}
+
+/// Collision settings of [SelectionStorage].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CollisionConfig {
+    /// How overlaps are resolved.
+    pub policy: CollisionPolicy,
+    /// Treat touching selections (one's `to` is right before another's
+    /// `from`) as colliding ones.
+    ///
+    /// Both ends of a selection are inclusive, so selections where one's `to`
+    /// equals another's `from` share a character and always collide. This
+    /// setting is about adjacent selections with nothing between them.
+    pub merge_touching: bool,
+}
+
+impl<T> SelectionStorage<T> {
+    /// Returns collision settings used by the storage.
+    pub fn collision_config(&self) -> CollisionConfig {
+        self.collisions
+    }
+
+    /// Sets collision settings to be used by the storage, returns previous
+    /// ones.
+    pub fn set_collision_config(&mut self, config: CollisionConfig) -> CollisionConfig {
+        std::mem::replace(&mut self.collisions, config)
+    }
+
+    /// Find the range of indicies of Selections that collide with the provided
+    /// one according to `merge_touching` setting, excluding `exclude` index if
+    /// any. `Err` case means no collisions and points to insertion position.
+    pub(crate) fn find_colliding_indicies(
+        &self,
+        line_lengths: &impl LineLength,
+        selection: &Selection<T>,
+        merge_touching: bool,
+        exclude: Option<SelectionIndex>,
+    ) -> Result<SelectionIndexRange, SelectionIndex> {
+        let (from, to) = if merge_touching {
+            (
+                selection.from.move_left(line_lengths, 1),
+                selection.to.move_right(line_lengths, 1),
+            )
+        } else {
+            (selection.from.clone(), selection.to.clone())
+        };
+
+        match exclude {
+            Some(idx) => self.find_overlapping_indicies_exlude(from.into(), to.into(), idx),
+            None => self.find_overlapping_indicies(from.into(), to.into()),
+        }
+    }
+
+    /// Trims `selection` to fit a gap between selections of `colliding` range
+    /// where its cursor is. `None` is returned if the cursor is inside of
+    /// an existing selection.
+    pub(crate) fn trim_to_fit(
+        &self,
+        line_lengths: &impl LineLength,
+        mut selection: Selection<T>,
+        colliding: SelectionIndexRange,
+    ) -> Option<Selection<T>> {
+        let cursor = selection.cursor().clone();
+        let mut gap_from: Option<&Position> = None;
+        let mut gap_to: Option<&Position> = None;
+
+        for s in &self.selections[colliding] {
+            if s.to < cursor {
+                gap_from = Some(&s.to);
+            } else if s.from > cursor {
+                gap_to = Some(&s.from);
+                break;
+            } else {
+                return None;
+            }
+        }
+
+        if let Some(prev_to) = gap_from.filter(|p| **p >= selection.from) {
+            selection.from = prev_to.move_right(line_lengths, 1).remove_sticky();
+        }
+        if let Some(next_from) = gap_to.filter(|p| **p <= selection.to) {
+            selection.to = next_from.move_left(line_lengths, 1).remove_sticky();
+        }
+
+        Some(selection)
+    }
+}
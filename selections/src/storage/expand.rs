@@ -0,0 +1,323 @@
+//! Expansion of [SelectionStorage] selections to enclosing syntactic units
+//! and shrinking them back.
+
+use std::mem;
+
+use super::{query::SelectionIndex, SelectionStorage};
+use crate::{LineLength, LineText, Position, Selection, SelectionDeltas, TextObject};
+
+/// Bounds of a selection.
+type Bounds = (Position, Position);
+
+/// Expansion history of a selection.
+pub(super) struct Expansion {
+    /// Bounds of the selection after the last expansion or shrinking, the
+    /// history is valid only while the selection has them.
+    current: Bounds,
+    /// Bounds before each expansion, the last one is the most recent.
+    previous: Vec<Bounds>,
+}
+
+/// Objects a selection could be expanded to, besides lines and the whole
+/// buffer, with their around flags.
+const EXPANSION_OBJECTS: [(TextObject, bool); 14] = [
+    (TextObject::Word, false),
+    (TextObject::Quote('"'), false),
+    (TextObject::Quote('\''), false),
+    (TextObject::Quote('`'), false),
+    (TextObject::Quote('"'), true),
+    (TextObject::Quote('\''), true),
+    (TextObject::Quote('`'), true),
+    (TextObject::Brackets('(', ')'), false),
+    (TextObject::Brackets('[', ']'), false),
+    (TextObject::Brackets('{', '}'), false),
+    (TextObject::Brackets('(', ')'), true),
+    (TextObject::Brackets('[', ']'), true),
+    (TextObject::Brackets('{', '}'), true),
+    (TextObject::Paragraph, false),
+];
+
+impl<T: Clone> SelectionStorage<T> {
+    /// Expands each selection to the smallest unit enclosing it: word, quoted
+    /// string or bracket contents, bracket pair, lines, paragraph and the
+    /// whole buffer. Overlapping results are resolved according to the
+    /// storage's [CollisionConfig](crate::CollisionConfig).
+    ///
+    /// Previous bounds are remembered per selection, so
+    /// [Self::shrink_selections] could walk them back until the selection is
+    /// changed by other operations.
+    pub fn expand_selections(&mut self, text: &impl LineText) -> SelectionDeltas<'_, T> {
+        let expanded: Vec<Option<Bounds>> =
+            self.selections.iter().map(|s| expansion(text, s)).collect();
+
+        // History is updated before selections change, while old bounds are at hand:
+        for (selection, new) in self.selections.iter().zip(&expanded) {
+            let (Some(id), Some(new)) = (selection.id, new) else {
+                continue;
+            };
+            let bounds = (selection.from.clone(), selection.to.clone());
+            match self.expansions.get_mut(&id) {
+                Some(expansion) if same_bounds(&expansion.current, &bounds) => {
+                    expansion.previous.push(bounds);
+                    expansion.current = new.clone();
+                }
+                _ => {
+                    self.expansions.insert(
+                        id,
+                        Expansion {
+                            current: new.clone(),
+                            previous: vec![bounds],
+                        },
+                    );
+                }
+            }
+        }
+
+        self.apply_bounds(text, expanded)
+    }
+
+    /// Shrinks each selection back to its bounds before the last
+    /// [Self::expand_selections]. Selections changed by other operations
+    /// since then are left as is.
+    pub fn shrink_selections(&mut self) -> SelectionDeltas<'_, T> {
+        self.tracked(|storage| {
+            // Selections are shrunk within their current bounds, so they're updated in
+            // place and can't collide. History left by a cancelled expansion doesn't
+            // match selection bounds and is skipped:
+            let mut shrunk = Vec::new();
+            for (idx, selection) in storage.selections.iter_mut().enumerate() {
+                let bounds = (selection.from.clone(), selection.to.clone());
+                let Some((from, to)) = selection
+                    .id
+                    .and_then(|id| storage.expansions.get_mut(&id))
+                    .filter(|expansion| same_bounds(&expansion.current, &bounds))
+                    .and_then(|expansion| {
+                        let previous = expansion.previous.pop()?;
+                        expansion.current = previous.clone();
+                        Some(previous)
+                    })
+                else {
+                    continue;
+                };
+                let new = Selection {
+                    from,
+                    to,
+                    ..selection.clone()
+                };
+                shrunk.push((idx, mem::replace(selection, new)));
+            }
+
+            for (idx, _) in shrunk.iter() {
+                storage.remember_id(*idx);
+            }
+            let mut deltas = SelectionDeltas::with_capacity(shrunk.len());
+            for (idx, old) in shrunk {
+                deltas.push_updated(old, idx, &storage.selections[idx], idx);
+            }
+            deltas
+        })
+    }
+
+    /// Sets new bounds for selections in storage order, `None` leaves a
+    /// selection as is.
+    fn apply_bounds(
+        &mut self,
+        text: &impl LineText,
+        bounds: Vec<Option<Bounds>>,
+    ) -> SelectionDeltas<'_, T> {
+        let mut bounds = bounds.into_iter();
+        self.map_all(text, |s| match bounds.next().flatten() {
+            Some((from, to)) => Selection {
+                from,
+                to,
+                ..s.clone()
+            },
+            None => s.clone(),
+        })
+    }
+}
+
+impl<T> SelectionStorage<T> {
+    /// Drops expansion history of a selection on `idx` if it was changed by
+    /// an operation other than expansion or shrinking.
+    pub(super) fn forget_changed_expansion(&mut self, idx: SelectionIndex) {
+        let selection = &self.selections[idx];
+        if let Some(id) = selection.id {
+            if self
+                .expansions
+                .get(&id)
+                .is_some_and(|e| !has_bounds(e, selection))
+            {
+                self.expansions.remove(&id);
+            }
+        }
+    }
+
+    /// Drops expansion history of all selections removed or changed by an
+    /// operation other than expansion or shrinking.
+    pub(super) fn forget_changed_expansions(&mut self) {
+        if self.expansions.is_empty() {
+            return;
+        }
+        let mut expansions = mem::take(&mut self.expansions);
+        self.expansions = self
+            .selections
+            .iter()
+            .filter_map(|s| {
+                let id = s.id?;
+                let expansion = expansions.remove(&id)?;
+                has_bounds(&expansion, s).then_some((id, expansion))
+            })
+            .collect();
+    }
+}
+
+/// Checks if expansion history is valid for a selection.
+fn has_bounds<T>(expansion: &Expansion, selection: &Selection<T>) -> bool {
+    expansion.current.0.weak_eq(&selection.from) && expansion.current.1.weak_eq(&selection.to)
+}
+
+/// Checks if bounds are equal ignoring sticky columns.
+fn same_bounds(a: &Bounds, b: &Bounds) -> bool {
+    a.0.weak_eq(&b.0) && a.1.weak_eq(&b.1)
+}
+
+/// The smallest unit strictly enclosing `selection`, if any.
+fn expansion<T>(text: &impl LineText, selection: &Selection<T>) -> Option<Bounds> {
+    let (from, to) = (&selection.from, &selection.to);
+    let is_enclosing = |(f, t): &Bounds| f < from || t > to;
+    let objects = EXPANSION_OBJECTS
+        .iter()
+        .filter_map(|(object, around)| object.enclosing(text, from, to, *around));
+    let lines = (
+        Position::new(from.line, 0),
+        Position::new(to.line, text.get_len(to.line)?),
+    );
+
+    // The whole buffer encloses anything else, so its length is not counted:
+    objects
+        .chain([lines])
+        .filter(is_enclosing)
+        .min_by_key(|bounds| bounds_len(text, bounds))
+        .or_else(|| {
+            let last_line = text.lines_count().checked_sub(1)?;
+            Some((
+                Position::new(0, 0),
+                Position::new(last_line, text.get_len(last_line)?),
+            ))
+            .filter(is_enclosing)
+        })
+}
+
+/// Number of characters within `bounds`, newlines included.
+fn bounds_len(line_lengths: &impl LineLength, (from, to): &Bounds) -> usize {
+    let lines: usize = (from.line..to.line)
+        .map(|line| line_lengths.get_len(line).unwrap_or_default() + 1)
+        .sum();
+    (lines + to.column + 1).saturating_sub(from.column)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        test_utils::{TestLineLengths, TestText},
+        CollisionConfig, CollisionPolicy,
+    };
+
+    fn bounds(storage: &SelectionStorage) -> Vec<((usize, usize), (usize, usize))> {
+        storage
+            .iter_all()
+            .map(|s| ((s.from.line, s.from.column), (s.to.line, s.to.column)))
+            .collect()
+    }
+
+    #[test]
+    fn expand_and_shrink() {
+        let text = TestText::new("x = f(a, \"bc de\");\ny\n\nz");
+        let mut storage = SelectionStorage::from_selections([Selection::new(
+            Position::new(0, 11),
+            Position::new(0, 11),
+        )]);
+
+        let steps = [
+            ((0, 10), (0, 11)),
+            ((0, 10), (0, 14)),
+            ((0, 9), (0, 15)),
+            ((0, 6), (0, 15)),
+            ((0, 5), (0, 16)),
+            ((0, 0), (0, 18)),
+            ((0, 0), (1, 1)),
+            ((0, 0), (3, 1)),
+        ];
+        for step in steps {
+            storage.expand_selections(&text);
+            assert_eq!(bounds(&storage), [step]);
+        }
+        assert_eq!(storage.expand_selections(&text).iter().count(), 0);
+
+        for step in steps.iter().rev().skip(1) {
+            storage.shrink_selections();
+            assert_eq!(bounds(&storage), [*step]);
+        }
+        storage.shrink_selections();
+        assert_eq!(bounds(&storage), [((0, 11), (0, 11))]);
+        assert_eq!(storage.shrink_selections().iter().count(), 0);
+    }
+
+    #[test]
+    fn moved_selection_forgets_history() {
+        let text = TestText::new("foo bar");
+        let mut line_lengths = TestLineLengths::new();
+        line_lengths.set(0, 7);
+
+        let mut storage = SelectionStorage::from_selections([Selection::new(
+            Position::new(0, 5),
+            Position::new(0, 5),
+        )]);
+        storage.expand_selections(&text);
+        assert_eq!(bounds(&storage), [((0, 4), (0, 6))]);
+
+        storage.move_left_single(&line_lengths, &Position::new(0, 4), 1, true);
+        assert_eq!(bounds(&storage), [((0, 4), (0, 5))]);
+        assert_eq!(storage.shrink_selections().iter().count(), 0);
+    }
+
+    #[test]
+    fn moved_back_selection_forgets_history() {
+        let text = TestText::new("foo bar");
+        let mut line_lengths = TestLineLengths::new();
+        line_lengths.set(0, 7);
+
+        let mut storage = SelectionStorage::from_selections([Selection::new(
+            Position::new(0, 5),
+            Position::new(0, 5),
+        )]);
+        storage.expand_selections(&text);
+        storage.move_right_single(&line_lengths, &Position::new(0, 4), 1, true);
+        storage.move_left_single(&line_lengths, &Position::new(0, 4), 1, true);
+        assert_eq!(bounds(&storage), [((0, 4), (0, 6))]);
+        assert_eq!(storage.shrink_selections().iter().count(), 0);
+    }
+
+    #[test]
+    fn cancelled_expansion_forgets_history() {
+        let text = TestText::new("foo bar");
+        let mut storage = SelectionStorage::from_selections([
+            Selection::new(Position::new(0, 1), Position::new(0, 1)),
+            Selection::new(Position::new(0, 5), Position::new(0, 5)),
+        ]);
+        storage.expand_selections(&text);
+        assert_eq!(bounds(&storage), [((0, 0), (0, 2)), ((0, 4), (0, 6))]);
+
+        storage.set_collision_config(CollisionConfig {
+            policy: CollisionPolicy::RejectNew,
+            ..Default::default()
+        });
+        assert_eq!(storage.expand_selections(&text).iter().count(), 0);
+        assert_eq!(storage.shrink_selections().iter().count(), 0);
+        assert_eq!(bounds(&storage), [((0, 0), (0, 2)), ((0, 4), (0, 6))]);
+    }
+}
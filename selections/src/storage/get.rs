@@ -1,22 +1,22 @@
 //! Operations to get selections using iterators.
 
 use core::slice;
-use std::cmp;
+use std::{cmp, ops::Range};
 
 use super::SelectionStorage;
 use crate::{Position, Selection};
 
 /// Iterator over selections.
-type SelectionsIter<'a> = slice::Iter<'a, Selection>;
+pub(super) type SelectionsIter<'a, T> = slice::Iter<'a, Selection<T>>;
 
-impl SelectionStorage {
+impl<T> SelectionStorage<T> {
     /// Returns iterator over all selections in the storage.
-    pub fn iter_all(&self) -> SelectionsIter {
+    pub fn iter_all(&self) -> SelectionsIter<'_, T> {
         self.selections.iter()
     }
 
     /// Returns iterator over selections starting from `line`
-    pub fn iter_from_line(&self, line: usize) -> SelectionsIter {
+    pub fn iter_from_line(&self, line: usize) -> SelectionsIter<'_, T> {
         let line_beginning = Position::new(line, 0);
 
         // Will point either on a selection that overlaps with the beginning of the line
@@ -34,6 +34,50 @@ impl SelectionStorage {
             _ => [].iter(),
         }
     }
+
+    /// Returns iterator over selections up to ones on `line` inclusive. As the
+    /// iterator is double-ended, it could be reversed to walk backwards from
+    /// the `line`.
+    pub fn iter_to_line(&self, line: usize) -> SelectionsIter<'_, T> {
+        let end_idx = self.lines_end_index(line.saturating_add(1));
+        self.selections[..end_idx].iter()
+    }
+
+    /// Returns iterator over selections overlapping `lines` range, such as a
+    /// viewport.
+    pub fn iter_in_lines(&self, lines: Range<usize>) -> SelectionsIter<'_, T> {
+        if lines.is_empty() {
+            return [].iter();
+        }
+
+        let end_idx = self.lines_end_index(lines.end);
+        let start_idx =
+            self.selections[..end_idx].partition_point(|s| s.to < Position::new(lines.start, 0));
+        self.selections[start_idx..end_idx].iter()
+    }
+
+    /// Returns a selection containing `position`, if any.
+    pub fn selection_at(&self, position: &Position) -> Option<&Selection<T>> {
+        self.find_overlapping_indicies(position.into(), position.into())
+            .ok()
+            .map(|range| &self.selections[*range.start()])
+    }
+
+    /// Returns iterator over selections intersecting with a range from `from`
+    /// to `to` inclusive.
+    pub fn selections_intersecting(&self, from: &Position, to: &Position) -> SelectionsIter<'_, T> {
+        match self.find_overlapping_indicies(from.into(), to.into()) {
+            Ok(range) => self.selections[range].iter(),
+            Err(_) => [].iter(),
+        }
+    }
+
+    /// Index of the first selection starting on `line` or later, all
+    /// selections before it start on previous lines.
+    fn lines_end_index(&self, line: usize) -> usize {
+        let line_beginning = Position::new(line, 0);
+        self.selections.partition_point(|s| s.from < line_beginning)
+    }
 }
 
 #[cfg(test)]
@@ -41,7 +85,10 @@ mod tests {
     use pretty_assertions::assert_eq;
 
     use super::*;
-    use crate::{Position, Selection};
+    use crate::{
+        test_utils::{self, selection},
+        Position, Selection,
+    };
 
     #[test]
     fn test_iter_all() {
@@ -122,4 +169,99 @@ mod tests {
         let mut iter = storage.iter_from_line(420);
         assert!(iter.next().is_none());
     }
+
+    fn storage() -> SelectionStorage {
+        test_utils::storage([
+            selection((1, 10), (1, 15)),
+            selection((1, 20), (2, 1)),
+            selection((2, 15), (2, 20)),
+            selection((3, 5), (5, 26)),
+            selection((7, 0), (7, 3)),
+        ])
+    }
+
+    fn collect_from<'a>(iter: impl Iterator<Item = &'a Selection>) -> Vec<Position> {
+        iter.map(|s| s.from.clone()).collect()
+    }
+
+    #[test]
+    fn test_iter_to_line() {
+        let storage = storage();
+        assert_eq!(
+            collect_from(storage.iter_to_line(2).rev()),
+            [
+                Position::new(2, 15),
+                Position::new(1, 20),
+                Position::new(1, 10),
+                Position::new(0, 0),
+            ]
+        );
+        assert_eq!(collect_from(storage.iter_to_line(0)), [Position::new(0, 0)]);
+        assert_eq!(collect_from(storage.iter_to_line(420)).len(), 6);
+        assert_eq!(collect_from(storage.iter_to_line(usize::MAX)).len(), 6);
+    }
+
+    #[test]
+    fn test_iter_in_lines() {
+        let storage = storage();
+        assert_eq!(
+            collect_from(storage.iter_in_lines(2..4)),
+            [
+                Position::new(1, 20),
+                Position::new(2, 15),
+                Position::new(3, 5),
+            ]
+        );
+        // Selection spanning over the whole range:
+        assert_eq!(
+            collect_from(storage.iter_in_lines(4..5)),
+            [Position::new(3, 5)]
+        );
+        // Nothing in between:
+        assert!(storage.iter_in_lines(6..7).next().is_none());
+        // Backwards:
+        assert_eq!(
+            collect_from(storage.iter_in_lines(0..2).rev()),
+            [
+                Position::new(1, 20),
+                Position::new(1, 10),
+                Position::new(0, 0),
+            ]
+        );
+        assert!(storage.iter_in_lines(3..3).next().is_none());
+    }
+
+    #[test]
+    fn test_selection_at() {
+        let storage = storage();
+        assert_eq!(
+            storage.selection_at(&Position::new(2, 0)).map(|s| &s.from),
+            Some(&Position::new(1, 20))
+        );
+        assert_eq!(
+            storage.selection_at(&Position::new(2, 15)).map(|s| &s.from),
+            Some(&Position::new(2, 15))
+        );
+        assert_eq!(storage.selection_at(&Position::new(2, 21)), None);
+        assert_eq!(storage.selection_at(&Position::new(69, 0)), None);
+    }
+
+    #[test]
+    fn test_selections_intersecting() {
+        let storage = storage();
+        assert_eq!(
+            collect_from(
+                storage.selections_intersecting(&Position::new(1, 15), &Position::new(2, 15))
+            ),
+            [
+                Position::new(1, 10),
+                Position::new(1, 20),
+                Position::new(2, 15),
+            ]
+        );
+        assert!(storage
+            .selections_intersecting(&Position::new(6, 0), &Position::new(6, 10))
+            .next()
+            .is_none());
+    }
 }
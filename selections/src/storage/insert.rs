@@ -1,32 +1,106 @@
 use std::{borrow::Cow, cmp, mem};
 
-use super::SelectionStorage;
-use crate::{Selection, SelectionDeltas};
+use super::{
+    query::{SelectionIndex, SelectionIndexRange},
+    CollisionConfig, CollisionPolicy, SelectionStorage,
+};
+use crate::{LineLength, Selection, SelectionDeltas};
 
-impl SelectionStorage {
+impl<T> SelectionStorage<T> {
     /// Insert a selection bounded by `from` and `to` positions. If inserted
     /// selection overlaps with an existing one(s) all will be merged into one.
-    pub fn insert(&mut self, selection: Selection) -> SelectionDeltas {
-        self.insert_internal(selection, false)
+    /// Inserted selection becomes primary.
+    pub fn insert(&mut self, selection: Selection<T>) -> SelectionDeltas<'_, T> {
+        self.tracked(|storage| {
+            let selection = storage.identify(selection);
+            let overlapping =
+                storage.find_overlapping_indicies((&selection.from).into(), (&selection.to).into());
+            storage.insert_internal(selection, CollisionPolicy::Merge, overlapping)
+        })
     }
 
     /// Insert a selection bounded by `from` and `to` positions. If inserted
     /// selection overlaps with an existing one(s) all will replaced by inserted
     /// one.
-    pub fn insert_replacing(&mut self, selection: Selection) -> SelectionDeltas {
-        self.insert_internal(selection, true)
+    pub fn insert_replacing(&mut self, selection: Selection<T>) -> SelectionDeltas<'_, T> {
+        self.tracked(|storage| {
+            let selection = storage.identify(selection);
+            let overlapping =
+                storage.find_overlapping_indicies((&selection.from).into(), (&selection.to).into());
+            storage.insert_internal(selection, CollisionPolicy::Replace, overlapping)
+        })
+    }
+
+    /// Insert a selection bounded by `from` and `to` positions resolving
+    /// collisions according to the storage's [CollisionConfig].
+    pub fn insert_with(
+        &mut self,
+        line_lengths: &impl LineLength,
+        selection: Selection<T>,
+    ) -> SelectionDeltas<'_, T> {
+        self.insert_with_config(line_lengths, selection, self.collisions)
+    }
+
+    /// Insert a selection bounded by `from` and `to` positions resolving
+    /// collisions according to provided `config`.
+    pub fn insert_with_config(
+        &mut self,
+        line_lengths: &impl LineLength,
+        selection: Selection<T>,
+        config: CollisionConfig,
+    ) -> SelectionDeltas<'_, T> {
+        self.tracked(|storage| {
+            let selection = storage.identify(selection);
+            let colliding = storage.find_colliding_indicies(
+                line_lengths,
+                &selection,
+                config.merge_touching,
+                None,
+            );
+
+            match (config.policy, colliding) {
+                (CollisionPolicy::TrimToFit, Ok(range)) => {
+                    match storage.trim_to_fit(line_lengths, selection, range) {
+                        Some(trimmed) => {
+                            let overlapping = storage.find_overlapping_indicies(
+                                (&trimmed.from).into(),
+                                (&trimmed.to).into(),
+                            );
+                            storage.insert_internal(trimmed, CollisionPolicy::Merge, overlapping)
+                        }
+                        None => Default::default(),
+                    }
+                }
+                (policy, colliding) => storage.insert_internal(selection, policy, colliding),
+            }
+        })
     }
 
     /// Insert a selection bounded by `from` and `to` positions. If inserted
-    /// selection overlaps with an existing one(s) it either will be replaced
-    /// (`replace == true`) or merged (`replace == false`).
-    fn insert_internal(&mut self, selection: Selection, replace: bool) -> SelectionDeltas {
-        let deltas = match self
-            .find_overlapping_indicies((&selection.from).into(), (&selection.to).into())
-        {
+    /// selection collides with an existing one(s) (`colliding` is `Ok`) it's
+    /// resolved according to `policy`. [CollisionPolicy::TrimToFit] is
+    /// expected to be handled before.
+    pub(super) fn insert_internal(
+        &mut self,
+        selection: Selection<T>,
+        policy: CollisionPolicy,
+        colliding: Result<SelectionIndexRange, SelectionIndex>,
+    ) -> SelectionDeltas<'_, T> {
+        let deltas = match colliding {
+            Ok(_)
+                if matches!(
+                    policy,
+                    CollisionPolicy::RejectNew | CollisionPolicy::KeepExisting
+                ) =>
+            {
+                // Inserted selection is dropped, nothing changes:
+                Default::default()
+            }
             Ok(overlapping_indicies) => {
-                // Build selection to insert depending on `replace` parameter:
-                let selection_to_insert = if replace {
+                // Build selection to insert depending on `policy` parameter:
+                let into = selection.id;
+                let merge = policy != CollisionPolicy::Replace;
+                let selection_to_insert = if !merge {
                     selection
                 } else {
                     let direction = selection.direction;
@@ -42,38 +116,47 @@ impl SelectionStorage {
                         from: min_from.into_owned(),
                         to: max_to.into_owned(),
                         direction,
+                        id: into,
+                        payload: selection.payload,
                     }
                 };
 
-                let mut deltas = SelectionDeltas::with_capacity(
-                    overlapping_indicies.end() - overlapping_indicies.start() + 2,
-                );
+                let start_idx = *overlapping_indicies.start();
+                let mut deltas =
+                    SelectionDeltas::with_capacity(overlapping_indicies.end() - start_idx + 2);
 
-                // Remove all overlapping selections except first one:
-                for s in self
+                // Remove all overlapping selections except first one, which is replaced with a
+                // new one:
+                let mut removed: Vec<Selection<T>> = self
                     .selections
-                    .drain(overlapping_indicies.start() + 1..=*overlapping_indicies.end())
-                {
-                    deltas.push_deleted(s);
-                }
-
-                // Replace first overlapping selection with a new one:
-                let mut old_first_selection = selection_to_insert;
-                mem::swap(
-                    &mut self.selections[*overlapping_indicies.start()],
-                    &mut old_first_selection,
+                    .drain(start_idx + 1..=*overlapping_indicies.end())
+                    .collect();
+                removed.insert(
+                    0,
+                    mem::replace(&mut self.selections[start_idx], selection_to_insert),
                 );
-                deltas.push_created(&self.selections[*overlapping_indicies.start()]);
-                deltas.push_deleted(old_first_selection);
+                self.set_primary_index(start_idx);
+
+                for (old_idx, s) in (start_idx..).zip(removed) {
+                    self.forget_id(&s);
+                    if merge {
+                        self.merge_payload(start_idx, &s);
+                    }
+                    deltas.push_merged(s, old_idx, into);
+                }
+                self.remember_id(start_idx);
+                deltas.push_created(&self.selections[start_idx], start_idx);
 
                 deltas
             }
             Err(index_to_insert) => {
                 // No overlaps found, just insert the selection:
                 self.selections.insert(index_to_insert, selection);
+                self.set_primary_index(index_to_insert);
+                self.remember_id(index_to_insert);
 
                 let mut deltas = SelectionDeltas::with_capacity(1);
-                deltas.push_created(&self.selections[index_to_insert]);
+                deltas.push_created(&self.selections[index_to_insert], index_to_insert);
                 deltas
             }
         };
@@ -86,7 +169,10 @@ impl SelectionStorage {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{test_utils::selections_test, Position};
+    use crate::{
+        test_utils::{selections_test, TestLineLengths},
+        Position,
+    };
 
     #[test]
     fn insert_reversed() {
@@ -391,4 +477,179 @@ mod tests {
             ]
         };
     }
+
+    #[test]
+    fn reject_new() {
+        selections_test! {
+            [(1, 3) - (3, 7)],
+            storage -> {
+                let line_lengths = TestLineLengths::new();
+                storage.insert_with_config(
+                    &line_lengths,
+                    Selection::new(Position::new(1, 4), Position::new(4, 5)),
+                    CollisionConfig {
+                        policy: CollisionPolicy::RejectNew,
+                        ..Default::default()
+                    },
+                )
+            },
+            [],
+            [(1, 3) - (3, 7)]
+        };
+    }
+
+    #[test]
+    fn keep_existing() {
+        selections_test! {
+            [(1, 3) - (3, 7)],
+            storage -> {
+                let line_lengths = TestLineLengths::new();
+                storage.set_collision_config(CollisionConfig {
+                    policy: CollisionPolicy::KeepExisting,
+                    ..Default::default()
+                });
+                storage.insert_with(
+                    &line_lengths,
+                    Selection::new(Position::new(0, 4), Position::new(1, 3)),
+                )
+            },
+            [],
+            [(1, 3) - (3, 7)]
+        };
+    }
+
+    #[test]
+    fn trim_to_fit() {
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+                (0, 8) - (0, 9),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 20);
+                storage.insert_with_config(
+                    &line_lengths,
+                    Selection::new(Position::new(0, 12), Position::new(0, 5)),
+                    CollisionConfig {
+                        policy: CollisionPolicy::TrimToFit,
+                        ..Default::default()
+                    },
+                )
+            },
+            [
+                Created((0, 7) - (0, 5)),
+            ],
+            [
+                (0, 0) - (0, 2),
+                (0, 7) - (0, 5),
+                (0, 8) - (0, 9),
+            ]
+        };
+    }
+
+    #[test]
+    fn trim_to_fit_multiline() {
+        selections_test! {
+            [
+                (0, 0) - (0, 4),
+                (1, 8) - (1, 9),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 4);
+                line_lengths.set(1, 20);
+                storage.insert_with_config(
+                    &line_lengths,
+                    Selection::new(Position::new(0, 2), Position::new(1, 3)),
+                    CollisionConfig {
+                        policy: CollisionPolicy::TrimToFit,
+                        ..Default::default()
+                    },
+                )
+            },
+            [
+                Created((1, 0) - (1, 3)),
+            ],
+            [
+                (0, 0) - (0, 4),
+                (1, 0) - (1, 3),
+                (1, 8) - (1, 9),
+            ]
+        };
+    }
+
+    #[test]
+    fn trim_to_fit_cursor_inside() {
+        selections_test! {
+            [(0, 8) - (0, 9)],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 20);
+                storage.insert_with_config(
+                    &line_lengths,
+                    Selection::new(Position::new(0, 2), Position::new(0, 9)),
+                    CollisionConfig {
+                        policy: CollisionPolicy::TrimToFit,
+                        ..Default::default()
+                    },
+                )
+            },
+            [],
+            [(0, 8) - (0, 9)]
+        };
+    }
+
+    #[test]
+    fn merge_touching() {
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+                (0, 6) - (0, 8),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 20);
+                storage.insert_with_config(
+                    &line_lengths,
+                    Selection::new(Position::new(0, 3), Position::new(0, 5)),
+                    CollisionConfig {
+                        merge_touching: true,
+                        ..Default::default()
+                    },
+                )
+            },
+            [
+                Deleted((0, 0) - (0, 2)),
+                Created((0, 0) - (0, 8)),
+                Deleted((0, 6) - (0, 8)),
+            ],
+            [(0, 0) - (0, 8)]
+        };
+    }
+
+    #[test]
+    fn merge_touching_multiline() {
+        selections_test! {
+            [(0, 0) - (0, 4)],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 4);
+                line_lengths.set(1, 20);
+                storage.insert_with_config(
+                    &line_lengths,
+                    Selection::new(Position::new(1, 0), Position::new(1, 2)),
+                    CollisionConfig {
+                        merge_touching: true,
+                        ..Default::default()
+                    },
+                )
+            },
+            [
+                Deleted((0, 0) - (0, 4)),
+                Created((0, 0) - (1, 2)),
+            ],
+            [(0, 0) - (1, 2)]
+        };
+    }
 }
@@ -0,0 +1,267 @@
+//! Identification of selections in [SelectionStorage].
+
+use super::{query::SelectionIndex, SelectionStorage};
+use crate::{Position, Selection, SelectionId};
+
+/// Reference to a selection in [SelectionStorage]: either its `from` position,
+/// which changes on movement, or its stable [SelectionId].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionKey<'a> {
+    /// Selection starting at the position.
+    Position(&'a Position),
+    /// Selection with the id.
+    Id(SelectionId),
+}
+
+impl<'a> From<&'a Position> for SelectionKey<'a> {
+    fn from(position: &'a Position) -> Self {
+        SelectionKey::Position(position)
+    }
+}
+
+impl From<SelectionId> for SelectionKey<'_> {
+    fn from(id: SelectionId) -> Self {
+        SelectionKey::Id(id)
+    }
+}
+
+impl<T> SelectionStorage<T> {
+    /// Returns a selection identified by `id` (see [SelectionKey]).
+    pub fn get<'k>(&self, id: impl Into<SelectionKey<'k>>) -> Option<&Selection<T>> {
+        self.find_index_by_key(id.into())
+            .map(|idx| &self.selections[idx])
+    }
+
+    /// Find an index of a selection identified by `key`.
+    pub(crate) fn find_index_by_key(&self, key: SelectionKey) -> Option<SelectionIndex> {
+        match key {
+            SelectionKey::Position(from) => self.find_index_by_id(from.into()),
+            SelectionKey::Id(id) => self
+                .ids
+                .get(&id)
+                .and_then(|from| self.find_index_by_id(from.into()))
+                .filter(|idx| self.selections[*idx].id == Some(id)),
+        }
+    }
+
+    /// Assigns a new id to a selection entering the storage.
+    pub(super) fn identify(&mut self, mut selection: Selection<T>) -> Selection<T> {
+        selection.id = Some(SelectionId(self.next_id));
+        self.next_id += 1;
+        selection
+    }
+
+    /// Keeps ids lookup table in sync with a selection on `idx` which was
+    /// created or moved.
+    pub(super) fn remember_id(&mut self, idx: SelectionIndex) {
+        let selection = &self.selections[idx];
+        if let Some(id) = selection.id {
+            self.ids.insert(id, selection.from.clone());
+        }
+        self.forget_changed_expansion(idx);
+    }
+
+    /// Drops a selection removed from the storage from ids lookup table.
+    pub(super) fn forget_id(&mut self, selection: &Selection<T>) {
+        if let Some(id) = selection.id {
+            self.ids.remove(&id);
+            self.expansions.remove(&id);
+        }
+    }
+
+    /// Rebuilds ids lookup table from scratch.
+    pub(super) fn rebuild_ids(&mut self) {
+        self.ids = self
+            .selections
+            .iter()
+            .filter_map(|s| Some((s.id?, s.from.clone())))
+            .collect();
+        self.forget_changed_expansions();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        test_utils::{selection, TestLineLengths},
+        CollisionConfig, CollisionPolicy, SelectionDelta, SelectionHistory, SelectionMerge,
+    };
+
+    fn insert(
+        storage: &mut SelectionStorage,
+        from: (usize, usize),
+        to: (usize, usize),
+    ) -> SelectionId {
+        let deltas = storage.insert(selection(from, to));
+        deltas
+            .iter()
+            .find_map(|d| match d {
+                SelectionDelta::Created(s) => s.selection_id(),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn id_survives_movement() {
+        let line_lengths = TestLineLengths::from_lengths([20; 5]);
+        let mut storage = SelectionStorage::new();
+        let id = insert(&mut storage, (1, 5), (1, 7));
+
+        storage.move_down_single(&line_lengths, id, 2, false);
+        storage.move_right_single(&line_lengths, id, 3, true);
+        assert_eq!(storage.get(id), Some(&selection((3, 7), (3, 10))));
+        assert_eq!(storage.get(id).and_then(Selection::selection_id), Some(id));
+        assert!(storage.set_primary(id));
+        assert_eq!(
+            storage.primary().and_then(Selection::selection_id),
+            Some(id)
+        );
+    }
+
+    #[test]
+    fn unknown_id() {
+        let line_lengths = TestLineLengths::from_lengths([20; 5]);
+        let mut storage = SelectionStorage::new();
+        let id = insert(&mut storage, (1, 5), (1, 7));
+        insert(&mut storage, (1, 0), (1, 9));
+
+        assert_eq!(storage.get(id), None);
+        assert!(storage
+            .move_left_single(&line_lengths, id, 1, false)
+            .into_owned()
+            .is_empty());
+    }
+
+    #[test]
+    fn insert_merge() {
+        let mut storage = SelectionStorage::new();
+        let a = insert(&mut storage, (1, 0), (1, 2));
+        let b = insert(&mut storage, (1, 5), (1, 7));
+        let deltas = storage.insert(selection((1, 1), (1, 6))).into_owned();
+        let c = storage
+            .get(&Position::new(1, 0))
+            .and_then(Selection::selection_id)
+            .unwrap();
+
+        assert_eq!(
+            deltas.merges(),
+            [
+                SelectionMerge {
+                    absorbed: a,
+                    into: c
+                },
+                SelectionMerge {
+                    absorbed: b,
+                    into: c
+                },
+            ]
+        );
+        assert_eq!(storage.get(a), None);
+
+        let replaced = storage.insert_replacing(selection((1, 0), (1, 1)));
+        let d = replaced
+            .iter()
+            .find_map(|delta| match delta {
+                SelectionDelta::Created(s) => s.selection_id(),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(
+            replaced.merges(),
+            [SelectionMerge {
+                absorbed: c,
+                into: d
+            }]
+        );
+    }
+
+    #[test]
+    fn movement_merge() {
+        let line_lengths = TestLineLengths::from_lengths([20; 5]);
+        let mut storage = SelectionStorage::new();
+        let a = insert(&mut storage, (1, 0), (1, 2));
+        let b = insert(&mut storage, (1, 5), (1, 7));
+
+        let deltas = storage.move_right_single(&line_lengths, a, 4, true);
+        assert_eq!(
+            deltas.merges(),
+            [SelectionMerge {
+                absorbed: b,
+                into: a
+            }]
+        );
+        assert_eq!(storage.get(a), Some(&selection((1, 0), (1, 7))));
+    }
+
+    #[test]
+    fn map_all_merge() {
+        let mut storage = SelectionStorage::new();
+        let a = insert(&mut storage, (1, 0), (1, 2));
+        let b = insert(&mut storage, (1, 5), (1, 7));
+
+        let deltas = storage.map_all(&TestLineLengths::new(), |s| {
+            Selection::new(s.from.clone(), Position::new(s.to.line, s.to.column + 3))
+        });
+        assert_eq!(
+            deltas.merges(),
+            [SelectionMerge {
+                absorbed: b,
+                into: a
+            }]
+        );
+        assert_eq!(storage.get(a), Some(&selection((1, 0), (1, 10))));
+    }
+
+    #[test]
+    fn map_all_replace() {
+        let mut storage = SelectionStorage::new();
+        let a = insert(&mut storage, (1, 0), (1, 2));
+        let b = insert(&mut storage, (1, 5), (1, 7));
+        let c = insert(&mut storage, (1, 9), (1, 10));
+
+        let config = CollisionConfig {
+            policy: CollisionPolicy::Replace,
+            ..Default::default()
+        };
+        let deltas = storage.map_all_with_config(&TestLineLengths::new(), config, |s| {
+            Selection::new(s.from.clone(), Position::new(s.to.line, s.to.column + 4))
+        });
+        assert_eq!(
+            deltas.merges(),
+            [
+                SelectionMerge {
+                    absorbed: a,
+                    into: c
+                },
+                SelectionMerge {
+                    absorbed: b,
+                    into: c
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn undo_and_rollback_restore_ids() {
+        let line_lengths = TestLineLengths::from_lengths([20; 5]);
+        let mut storage = SelectionStorage::new();
+        let a = insert(&mut storage, (1, 0), (1, 2));
+        let b = insert(&mut storage, (1, 5), (1, 7));
+
+        let mut history = SelectionHistory::new();
+        history.record(storage.move_right_single(&line_lengths, a, 4, true));
+        history.commit_step();
+        history.undo_selection(&mut storage);
+        assert_eq!(storage.get(b), Some(&selection((1, 5), (1, 7))));
+
+        storage.begin_transaction();
+        storage.move_down_single(&line_lengths, b, 1, false);
+        storage.rollback();
+        assert_eq!(storage.get(b), Some(&selection((1, 5), (1, 7))));
+        assert_eq!(storage.get(a), Some(&selection((1, 0), (1, 2))));
+    }
+}
@@ -0,0 +1,611 @@
+//! Application of an arbitrary transformation to all selections of
+//! [SelectionStorage].
+
+use std::{cmp::Reverse, collections::BinaryHeap, mem};
+
+use super::{query::SelectionIndex, CollisionConfig, CollisionPolicy, SelectionStorage};
+use crate::{LineLength, Position, Selection, SelectionDeltas};
+
+impl<T> SelectionStorage<T> {
+    /// Applies `f` to every selection, restores ordering and resolves overlaps
+    /// according to the storage's [CollisionConfig].
+    ///
+    /// Transformed selections are reported as `Updated` (unless transformation
+    /// didn't change them), and ones lost on collision are `Deleted`. If
+    /// primary selection is lost, the one which absorbed or replaced it
+    /// becomes primary.
+    pub fn map_all(
+        &mut self,
+        line_lengths: &impl LineLength,
+        f: impl FnMut(&Selection<T>) -> Selection<T>,
+    ) -> SelectionDeltas<'_, T> {
+        self.map_all_with_config(line_lengths, self.collisions, f)
+    }
+
+    /// Applies `f` to every selection just like [SelectionStorage::map_all]
+    /// does, resolving overlaps according to provided `config`.
+    ///
+    /// Of two colliding selections the one that comes later in storage order
+    /// (before transformation) is treated as a new one: it's dropped with
+    /// [CollisionPolicy::KeepExisting], replaces the earlier one with
+    /// [CollisionPolicy::Replace] and is trimmed with
+    /// [CollisionPolicy::TrimToFit]. On [CollisionPolicy::Merge] the leftmost
+    /// selection absorbs others, and any collision cancels the whole
+    /// operation on [CollisionPolicy::RejectNew].
+    pub fn map_all_with_config(
+        &mut self,
+        line_lengths: &impl LineLength,
+        config: CollisionConfig,
+        f: impl FnMut(&Selection<T>) -> Selection<T>,
+    ) -> SelectionDeltas<'_, T> {
+        self.tracked(|storage| storage.map_all_internal(line_lengths, config, f))
+    }
+
+    /// Common logic of transformation of all selections.
+    fn map_all_internal(
+        &mut self,
+        line_lengths: &impl LineLength,
+        config: CollisionConfig,
+        mut f: impl FnMut(&Selection<T>) -> Selection<T>,
+    ) -> SelectionDeltas<'_, T> {
+        // Transformed selections keep their ids:
+        let mut mapped: Vec<Option<Selection<T>>> = self
+            .selections
+            .iter()
+            .map(|s| Some(Selection { id: s.id, ..f(s) }))
+            .collect();
+        // Selections are swept in order of their `from` positions, on `from` collision
+        // storage order is kept. Trimmed selections are swept again:
+        let mut pending: BinaryHeap<Reverse<(Position, SelectionIndex)>> = mapped
+            .iter()
+            .flatten()
+            .map(|s| s.from.clone())
+            .zip(0..)
+            .map(Reverse)
+            .collect();
+        let collides = |last: &Selection<T>, selection: &Selection<T>| {
+            if config.merge_touching {
+                last.to.move_right(line_lengths, 1) >= selection.from
+            } else {
+                last.to >= selection.from
+            }
+        };
+
+        // Primary selection is tracked by its index before the transformation, if it's
+        // lost on collision the survivor becomes primary.
+        let mut primary_idx = self.primary_index();
+
+        let mut merged_into: Vec<Option<SelectionIndex>> = vec![None; mapped.len()];
+        let mut survivors: Vec<(SelectionIndex, Selection<T>)> = Vec::with_capacity(mapped.len());
+        while let Some(Reverse((from, idx))) = pending.pop() {
+            let Some(selection) = mapped[idx].take() else {
+                continue;
+            };
+            // Only the last survivor might collide as survivors are sorted and don't
+            // overlap with each other.
+            let (last_idx, mut last) = match survivors.pop() {
+                Some(last) if collides(&last.1, &selection) => last,
+                last => {
+                    survivors.extend(last);
+                    survivors.push((idx, selection));
+                    continue;
+                }
+            };
+
+            match config.policy {
+                CollisionPolicy::Merge => {
+                    if selection.to > last.to {
+                        last.to = selection.to;
+                    }
+                    if let Some(merge) = self.payload_merge.as_mut() {
+                        merge(&mut last.payload, &selection.payload);
+                    }
+                    merged_into[idx] = Some(last_idx);
+                    if primary_idx == Some(idx) {
+                        primary_idx = Some(last_idx);
+                    }
+                    survivors.push((last_idx, last));
+                }
+                CollisionPolicy::RejectNew => return Default::default(),
+                CollisionPolicy::Replace | CollisionPolicy::KeepExisting => {
+                    let (winner, loser) =
+                        if (last_idx < idx) == (config.policy == CollisionPolicy::Replace) {
+                            ((idx, selection), last_idx)
+                        } else {
+                            ((last_idx, last), idx)
+                        };
+                    if config.policy == CollisionPolicy::Replace {
+                        merged_into[loser] = Some(winner.0);
+                    }
+                    if primary_idx == Some(loser) {
+                        primary_idx = Some(winner.0);
+                    }
+                    survivors.push(winner);
+                }
+                CollisionPolicy::TrimToFit => {
+                    let (mut new, existing) = if last_idx < idx {
+                        ((idx, selection), (last_idx, last))
+                    } else {
+                        ((last_idx, last), (idx, selection))
+                    };
+                    if !trim_to_gap(line_lengths, &mut new.1, &existing.1) {
+                        return Default::default();
+                    }
+
+                    // Trimmed pair doesn't overlap anymore, but the rightmost one is swept
+                    // again if it starts after selections yet to come.
+                    let (left, right) = if new.1.from < existing.1.from {
+                        (new, existing)
+                    } else {
+                        (existing, new)
+                    };
+                    survivors.push(left);
+                    if right.1.from > from {
+                        pending.push(Reverse((right.1.from.clone(), right.0)));
+                        mapped[right.0] = Some(right.1);
+                    } else {
+                        survivors.push(right);
+                    }
+                }
+            }
+        }
+
+        // For each selection of the previous state find its new index, if any.
+        let mut new_indicies: Vec<Option<SelectionIndex>> = vec![None; self.selections.len()];
+        for (new_idx, (old_idx, _)) in survivors.iter().enumerate() {
+            new_indicies[*old_idx] = Some(new_idx);
+        }
+
+        let old_selections = mem::replace(
+            &mut self.selections,
+            survivors.into_iter().map(|(_, s)| s).collect(),
+        );
+        if let Some(idx) = primary_idx.and_then(|idx| new_indicies[idx]) {
+            self.set_primary_index(idx);
+        }
+        self.rebuild_ids();
+
+        let old_ids: Vec<_> = old_selections.iter().map(|s| s.id).collect();
+        // A replacing selection might be replaced later on, lost ones are reported as
+        // merged into the survivor:
+        let survivor = |mut idx: SelectionIndex| {
+            while let Some(into) = merged_into[idx] {
+                idx = into;
+            }
+            old_ids[idx]
+        };
+        let mut deltas = SelectionDeltas::with_capacity(old_selections.len());
+        for (old_idx, (old, new_idx)) in old_selections.into_iter().zip(new_indicies).enumerate() {
+            match new_idx {
+                Some(idx) if self.selections[idx] == old => {}
+                Some(idx) => deltas.push_updated(old, old_idx, &self.selections[idx], idx),
+                None if merged_into[old_idx].is_some() => {
+                    deltas.push_merged(old, old_idx, survivor(old_idx))
+                }
+                None => deltas.push_deleted(old, old_idx),
+            }
+        }
+
+        debug_assert!(self.is_state_correct());
+        deltas
+    }
+}
+
+/// Trims `new` selection to fit a gap next to `existing` one where its cursor
+/// is. Returns `false` if the cursor is inside of `existing` selection.
+fn trim_to_gap<T>(
+    line_lengths: &impl LineLength,
+    new: &mut Selection<T>,
+    existing: &Selection<T>,
+) -> bool {
+    let cursor = new.cursor();
+    if *cursor < existing.from {
+        if new.to >= existing.from {
+            new.to = existing.from.move_left(line_lengths, 1).remove_sticky();
+        }
+    } else if *cursor > existing.to {
+        if new.from <= existing.to {
+            new.from = existing.to.move_right(line_lengths, 1).remove_sticky();
+        }
+    } else {
+        return false;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_utils::{selections_test, TestLineLengths},
+        Position,
+    };
+
+    fn shift(selection: &Selection, columns: usize) -> Selection {
+        let mut selection = selection.clone();
+        selection.from.column += columns;
+        selection.to.column += columns;
+        selection
+    }
+
+    #[test]
+    fn no_collisions() {
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+                (1, 5) - (1, 3),
+            ],
+            storage -> { storage.map_all(&TestLineLengths::new(), |s| shift(s, 1)) },
+            [
+                Updated {
+                    old: (0, 0) - (0, 2),
+                    new: (0, 1) - (0, 3),
+                },
+                Updated {
+                    old: (1, 5) - (1, 3),
+                    new: (1, 6) - (1, 4),
+                },
+            ],
+            [
+                (0, 1) - (0, 3),
+                (1, 6) - (1, 4),
+            ]
+        };
+    }
+
+    #[test]
+    fn unchanged_are_skipped() {
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+                (1, 3) - (1, 5),
+            ],
+            storage -> {
+                storage.map_all(&TestLineLengths::new(), |s| {
+                    if s.from.line == 1 {
+                        shift(s, 2)
+                    } else {
+                        s.clone()
+                    }
+                })
+            },
+            [
+                Updated {
+                    old: (1, 3) - (1, 5),
+                    new: (1, 5) - (1, 7),
+                },
+            ],
+            [
+                (0, 0) - (0, 2),
+                (1, 5) - (1, 7),
+            ]
+        };
+    }
+
+    #[test]
+    fn reorder() {
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+                (1, 3) - (1, 5),
+            ],
+            storage -> {
+                storage.map_all(&TestLineLengths::new(), |s| {
+                    if s.from.line == 0 {
+                        Selection::new(Position::new(2, 0), Position::new(2, 1))
+                    } else {
+                        s.clone()
+                    }
+                })
+            },
+            [
+                Updated {
+                    old: (0, 0) - (0, 2),
+                    new: (2, 0) - (2, 1),
+                },
+            ],
+            [
+                (1, 3) - (1, 5),
+                (2, 0) - (2, 1),
+            ]
+        };
+    }
+
+    #[test]
+    fn collision_merge() {
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+                (0, 4) - (0, 6),
+                (0, 8) - (0, 9),
+                (1, 0) - (1, 1),
+            ],
+            storage -> {
+                storage.map_all(&TestLineLengths::new(), |s| {
+                    let mut s = s.clone();
+                    if s.from.line == 0 {
+                        s.to.column += 2;
+                    }
+                    s
+                })
+            },
+            [
+                Updated {
+                    old: (0, 0) - (0, 2),
+                    new: (0, 0) - (0, 11),
+                },
+                Deleted((0, 4) - (0, 6)),
+                Deleted((0, 8) - (0, 9)),
+            ],
+            [
+                (0, 0) - (0, 11),
+                (1, 0) - (1, 1),
+            ]
+        };
+    }
+
+    #[test]
+    fn collision_replace() {
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+                (0, 4) - (0, 6),
+                (0, 8) - (0, 9),
+                (1, 0) - (1, 1),
+            ],
+            storage -> {
+                let config = CollisionConfig {
+                    policy: CollisionPolicy::Replace,
+                    ..Default::default()
+                };
+                storage.map_all_with_config(&TestLineLengths::new(), config, |s| {
+                    let mut s = s.clone();
+                    if s.from.line == 0 {
+                        s.to.column += 2;
+                    }
+                    s
+                })
+            },
+            [
+                Deleted((0, 0) - (0, 2)),
+                Deleted((0, 4) - (0, 6)),
+                Updated {
+                    old: (0, 8) - (0, 9),
+                    new: (0, 8) - (0, 11),
+                },
+            ],
+            [
+                (0, 8) - (0, 11),
+                (1, 0) - (1, 1),
+            ]
+        };
+    }
+
+    #[test]
+    fn collision_replace_reordered() {
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+                (1, 0) - (1, 2),
+            ],
+            storage -> {
+                let config = CollisionConfig {
+                    policy: CollisionPolicy::Replace,
+                    ..Default::default()
+                };
+                storage.map_all_with_config(&TestLineLengths::new(), config, |s| {
+                    if s.from.line == 0 {
+                        Selection::new(Position::new(1, 1), Position::new(1, 5))
+                    } else {
+                        Selection::new(Position::new(1, 0), Position::new(1, 3))
+                    }
+                })
+            },
+            [
+                Deleted((0, 0) - (0, 2)),
+                Updated {
+                    old: (1, 0) - (1, 2),
+                    new: (1, 0) - (1, 3),
+                },
+            ],
+            [
+                (1, 0) - (1, 3),
+            ]
+        };
+    }
+
+    /// Extends selections of the first line by 2 characters.
+    fn extend_first_line(selection: &Selection) -> Selection {
+        let mut selection = selection.clone();
+        if selection.from.line == 0 {
+            selection.to.column += 2;
+        }
+        selection
+    }
+
+    #[test]
+    fn collision_keep_existing() {
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+                (0, 4) - (0, 6),
+                (1, 0) - (1, 1),
+            ],
+            storage -> {
+                let config = CollisionConfig {
+                    policy: CollisionPolicy::KeepExisting,
+                    ..Default::default()
+                };
+                storage.map_all_with_config(&TestLineLengths::new(), config, extend_first_line)
+            },
+            [
+                Updated {
+                    old: (0, 0) - (0, 2),
+                    new: (0, 0) - (0, 4),
+                },
+                Deleted((0, 4) - (0, 6)),
+            ],
+            [
+                (0, 0) - (0, 4),
+                (1, 0) - (1, 1),
+            ]
+        };
+    }
+
+    #[test]
+    fn collision_reject_new() {
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+                (0, 4) - (0, 6),
+                (1, 0) - (1, 1),
+            ],
+            storage -> {
+                let config = CollisionConfig {
+                    policy: CollisionPolicy::RejectNew,
+                    ..Default::default()
+                };
+                storage.map_all_with_config(&TestLineLengths::new(), config, extend_first_line)
+            },
+            [],
+            [
+                (0, 0) - (0, 2),
+                (0, 4) - (0, 6),
+                (1, 0) - (1, 1),
+            ]
+        };
+    }
+
+    #[test]
+    fn collision_trim_to_fit() {
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+                (0, 4) - (0, 6),
+                (1, 0) - (1, 1),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 20);
+                let config = CollisionConfig {
+                    policy: CollisionPolicy::TrimToFit,
+                    ..Default::default()
+                };
+                storage.map_all_with_config(&line_lengths, config, extend_first_line)
+            },
+            [
+                Updated {
+                    old: (0, 0) - (0, 2),
+                    new: (0, 0) - (0, 4),
+                },
+                Updated {
+                    old: (0, 4) - (0, 6),
+                    new: (0, 5) - (0, 8),
+                },
+            ],
+            [
+                (0, 0) - (0, 4),
+                (0, 5) - (0, 8),
+                (1, 0) - (1, 1),
+            ]
+        };
+    }
+
+    #[test]
+    fn collision_trim_to_fit_chain() {
+        selections_test! {
+            [
+                (0, 0) - (0, 10),
+                (1, 0) - (1, 1),
+                (2, 0) - (2, 1),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 20);
+                let config = CollisionConfig {
+                    policy: CollisionPolicy::TrimToFit,
+                    ..Default::default()
+                };
+                storage.map_all_with_config(&line_lengths, config, |s| match s.from.line {
+                    0 => s.clone(),
+                    1 => Selection::new(Position::new(0, 5), Position::new(0, 12)),
+                    _ => Selection::new(Position::new(0, 6), Position::new(0, 15)),
+                })
+            },
+            [
+                Updated {
+                    old: (1, 0) - (1, 1),
+                    new: (0, 11) - (0, 12),
+                },
+                Updated {
+                    old: (2, 0) - (2, 1),
+                    new: (0, 13) - (0, 15),
+                },
+            ],
+            [
+                (0, 0) - (0, 10),
+                (0, 11) - (0, 12),
+                (0, 13) - (0, 15),
+            ]
+        };
+    }
+
+    #[test]
+    fn collision_trim_to_fit_cursor_inside() {
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+                (0, 6) - (0, 4),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 20);
+                let config = CollisionConfig {
+                    policy: CollisionPolicy::TrimToFit,
+                    ..Default::default()
+                };
+                storage.map_all_with_config(&line_lengths, config, extend_first_line)
+            },
+            [],
+            [
+                (0, 0) - (0, 2),
+                (0, 6) - (0, 4),
+            ]
+        };
+    }
+
+    #[test]
+    fn collision_merge_touching() {
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+                (0, 4) - (0, 6),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 20);
+                let config = CollisionConfig {
+                    merge_touching: true,
+                    ..Default::default()
+                };
+                storage.map_all_with_config(&line_lengths, config, |s| {
+                    let mut s = s.clone();
+                    if s.from.column == 0 {
+                        s.to.column += 1;
+                    }
+                    s
+                })
+            },
+            [
+                Updated {
+                    old: (0, 0) - (0, 2),
+                    new: (0, 0) - (0, 6),
+                },
+                Deleted((0, 4) - (0, 6)),
+            ],
+            [
+                (0, 0) - (0, 6),
+            ]
+        };
+    }
+}
@@ -0,0 +1,248 @@
+//! Selection of pattern matches inside of [SelectionStorage] selections.
+
+use std::{mem, ops::Range};
+
+use super::{clipboard::selection_text, SelectionStorage};
+use crate::{LineText, Position, Selection, SelectionDeltas};
+
+/// Pattern to search for in text, could be implemented for regular
+/// expressions or any other search engine.
+pub trait Matcher {
+    /// Returns byte ranges of non-overlapping matches in `haystack` ordered
+    /// by their positions.
+    fn find_matches(&self, haystack: &str) -> Vec<Range<usize>>;
+}
+
+/// Literal string pattern.
+impl Matcher for str {
+    fn find_matches(&self, haystack: &str) -> Vec<Range<usize>> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+        haystack
+            .match_indices(self)
+            .map(|(start, m)| start..start + m.len())
+            .collect()
+    }
+}
+
+/// Single character pattern.
+impl Matcher for char {
+    fn find_matches(&self, haystack: &str) -> Vec<Range<usize>> {
+        haystack
+            .match_indices(*self)
+            .map(|(start, m)| start..start + m.len())
+            .collect()
+    }
+}
+
+impl<T: Clone> SelectionStorage<T> {
+    /// Replaces each selection with selections of `matcher` matches inside of
+    /// it, matches carry the payload of their selection. Selections without
+    /// matches are dropped, but if nothing matches at all the storage remains
+    /// unchanged.
+    ///
+    /// Replaced selections are reported as `Deleted` and matches are
+    /// `Created`. The first match of the primary selection becomes primary.
+    pub fn select_matches<M: Matcher + ?Sized>(
+        &mut self,
+        text: &impl LineText,
+        matcher: &M,
+    ) -> SelectionDeltas<'_, T> {
+        self.tracked(|storage| storage.select_matches_internal(text, matcher))
+    }
+
+    fn select_matches_internal<M: Matcher + ?Sized>(
+        &mut self,
+        text: &impl LineText,
+        matcher: &M,
+    ) -> SelectionDeltas<'_, T> {
+        let primary_from = self.primary().map(|s| s.from.clone());
+        let mut matches = Vec::new();
+        let mut primary_idx = None;
+        for selection in self.selections.iter() {
+            let haystack = selection_text(text, selection);
+            let ranges = matcher.find_matches(&haystack);
+            if primary_idx.is_none() && self.is_primary(selection) && !ranges.is_empty() {
+                primary_idx = Some(matches.len());
+            }
+            matches.extend(sub_selections(selection, &haystack, ranges));
+        }
+        if matches.is_empty() {
+            return SelectionDeltas::new();
+        }
+
+        let matches = matches.into_iter().map(|s| self.identify(s)).collect();
+        let old_selections = mem::replace(&mut self.selections, matches);
+        // If the primary selection had no matches the closest one becomes primary:
+        let primary_idx = primary_idx.unwrap_or_else(|| {
+            let from = primary_from.as_ref().unwrap_or(&old_selections[0].from);
+            self.selections
+                .partition_point(|s| &s.from < from)
+                .min(self.selections.len() - 1)
+        });
+        self.set_primary_index(primary_idx);
+        self.rebuild_ids();
+
+        let mut deltas =
+            SelectionDeltas::with_capacity(old_selections.len() + self.selections.len());
+        for (old_idx, old) in old_selections.into_iter().enumerate() {
+            deltas.push_deleted(old, old_idx);
+        }
+        for (new_idx, new) in self.selections.iter().enumerate() {
+            deltas.push_created(new, new_idx);
+        }
+
+        debug_assert!(self.is_state_correct());
+        deltas
+    }
+}
+
+/// Turns byte `ranges` of `haystack` which is a text of `selection` into
+/// selections, empty ranges are skipped.
+pub(super) fn sub_selections<'a, T: Clone>(
+    selection: &'a Selection<T>,
+    haystack: &'a str,
+    ranges: Vec<Range<usize>>,
+) -> impl Iterator<Item = Selection<T>> + 'a {
+    let mut position = Position::new(selection.from.line, selection.from.column);
+    let mut offset = 0;
+    // Ranges are ordered, so positions are found in a single pass over the text:
+    let mut advance = move |target: usize| {
+        for ch in haystack[offset..target].chars() {
+            if ch == '\n' {
+                position.line += 1;
+                position.column = 0;
+            } else {
+                position.column += 1;
+            }
+        }
+        offset = target;
+        position.clone()
+    };
+
+    ranges
+        .into_iter()
+        .filter(|range| !range.is_empty())
+        .map(move |range| {
+            let from = advance(range.start);
+            let last = haystack[range.clone()]
+                .char_indices()
+                .next_back()
+                .map(|(idx, _)| range.start + idx)
+                .unwrap_or(range.start);
+            let to = advance(last);
+            Selection::with_payload(from, to, selection.payload.clone())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::test_utils::{selections_test, TestText};
+
+    #[test]
+    fn literal_matches() {
+        let text = TestText::new("foo bar foo\nbaz foo\nfoo");
+        selections_test! {
+            [
+                (0, 0) - (0, 10),
+                (1, 0) - (2, 2),
+            ],
+            storage -> { storage.select_matches(&text, "foo") },
+            [
+                Deleted((0, 0) - (0, 10)),
+                Created((0, 0) - (0, 2)),
+                Created((0, 8) - (0, 10)),
+                Deleted((1, 0) - (2, 2)),
+                Created((1, 4) - (1, 6)),
+                Created((2, 0) - (2, 2)),
+            ],
+            [
+                (0, 0) - (0, 2),
+                (0, 8) - (0, 10),
+                (1, 4) - (1, 6),
+                (2, 0) - (2, 2),
+            ]
+        };
+    }
+
+    #[test]
+    fn multiline_matches() {
+        let text = TestText::new("ab\ncd\nab\ncd");
+        selections_test! {
+            [
+                (0, 1) - (3, 0),
+            ],
+            storage -> { storage.select_matches(&text, "b\nc") },
+            [
+                Deleted((0, 1) - (3, 0)),
+                Created((0, 1) - (1, 0)),
+                Created((2, 1) - (3, 0)),
+            ],
+            [
+                (0, 1) - (1, 0),
+                (2, 1) - (3, 0),
+            ]
+        };
+    }
+
+    #[test]
+    fn no_matches() {
+        let text = TestText::new("foo bar");
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+                (0, 4) - (0, 6),
+            ],
+            storage -> { storage.select_matches(&text, "baz") },
+            [],
+            [
+                (0, 0) - (0, 2),
+                (0, 4) - (0, 6),
+            ]
+        };
+    }
+
+    #[test]
+    fn primary_and_payload() {
+        let text = TestText::new("a-b c-d");
+        let storage = || {
+            let mut storage = SelectionStorage::with_payload(0);
+            storage.insert(Selection::with_payload(
+                Position::new(0, 4),
+                Position::new(0, 6),
+                1,
+            ));
+            storage.insert(Selection::with_payload(
+                Position::new(0, 0),
+                Position::new(0, 2),
+                2,
+            ));
+            storage
+        };
+        let summary = |storage: &SelectionStorage<i32>| {
+            storage
+                .iter_all()
+                .map(|s| (s.from.clone(), *s.payload(), storage.is_primary(s)))
+                .collect::<Vec<_>>()
+        };
+
+        let mut matched = storage();
+        matched.select_matches(&text, &'-');
+        assert_eq!(
+            summary(&matched),
+            [
+                (Position::new(0, 1), 2, true),
+                (Position::new(0, 5), 1, false)
+            ]
+        );
+
+        // Primary selection has no matches:
+        let mut matched = storage();
+        matched.select_matches(&text, "c");
+        assert_eq!(summary(&matched), [(Position::new(0, 4), 1, true)]);
+    }
+}
@@ -1,226 +1,440 @@
 //! [SelectionStorage] movements implementation module.
 
-use std::{cmp, mem};
+use std::mem;
 
 use super::{
-    query::{SelectionIndex, SelectionIndexRange},
-    SelectionStorage,
+    query::SelectionIndexRange, CollisionConfig, CollisionPolicy, SelectionKey, SelectionStorage,
+};
+use crate::{
+    text::{sentence_stops_around, BufferText},
+    LineLength, LineText, Selection, SelectionDeltas,
 };
-use crate::{LineLength, Position, Selection, SelectionDeltas};
 
-/// Structure that handles corner cases for general single selection movement in
-/// [SelectionStorage].
+/// Where a moved selection goes, as behaviour on collisions differs whether
+/// movement happens towards buffer beginning or not.
+#[derive(Clone, Copy, PartialEq, Eq)]
 enum MovementDirection {
-    Left,
-    Right,
-    Up,
-    Down,
+    TowardsBeginning,
+    TowardsEnd,
 }
 
 impl MovementDirection {
-    /// Boolean flag as most of the times behaviour differs whether movement
-    /// happens towards buffer beginning or not.
-    fn is_towards_beginning(&self) -> bool {
-        match self {
-            MovementDirection::Left | MovementDirection::Up => true,
-            MovementDirection::Right | MovementDirection::Down => false,
-        }
-    }
-
-    /// Apply appropriate function call to get new selection.
-    fn new_selection_state(
-        &self,
-        selection: &Selection,
-        line_length: &impl LineLength,
-        n: usize,
-        extend: bool,
-    ) -> Selection {
-        match self {
-            MovementDirection::Left => selection.move_left(line_length, n, extend),
-            MovementDirection::Right => selection.move_right(line_length, n, extend),
-            MovementDirection::Up => selection.move_up(line_length, n, extend),
-            MovementDirection::Down => selection.move_down(line_length, n, extend),
-        }
-    }
-
-    /// Depending on direction of movement in case of no overlaps other
-    /// selections between old a new positions should be either moved towards
-    /// beginning or end.
-    ///
-    /// The difference between left and right movement is that right border of
-    /// rotation during left movement was an index of actual selection (previous
-    /// state), but in case of right rotation right border is found via
-    /// [Self::find_overlapping_indicies_exlude] which points to insertion
-    /// position, which is the index of selection to insert _before_ it (or
-    /// may be out of bounds if insertion should happen to the vector's
-    /// end), so that means actual selection index for right border is
-    /// decremented in case of movement towards buffer end.
-    fn fix_step_over_rotation(
-        &self,
-        selections: &mut [Selection],
-        old_index: SelectionIndex,
-        new_index: SelectionIndex,
-    ) {
-        if self.is_towards_beginning() {
-            selections[new_index..=old_index].rotate_right(1);
+    /// Direction of movement from `old` to `new` selection state, it's defined
+    /// by the cursor as it's what motions move.
+    fn of<T>(old: &Selection<T>, new: &Selection<T>) -> Self {
+        if new.cursor() < old.cursor() {
+            MovementDirection::TowardsBeginning
         } else {
-            selections[old_index..new_index].rotate_left(1);
+            MovementDirection::TowardsEnd
         }
     }
 
     /// Extends new selections in case of overlap by it's edge.
-    fn fix_extend_overlap(
+    fn fix_extend_overlap<T>(
         &self,
-        selections: &[Selection],
+        selections: &[Selection<T>],
         overlap_indicies: SelectionIndexRange,
-        new_selection: &mut Selection,
+        new_selection: &mut Selection<T>,
     ) {
         let start_idx = *overlap_indicies.start();
         let end_idx = *overlap_indicies.end();
 
-        if self.is_towards_beginning() {
-            if selections[start_idx].from < new_selection.from {
-                new_selection.from = selections[start_idx].from.clone();
+        match self {
+            MovementDirection::TowardsBeginning => {
+                if selections[start_idx].from < new_selection.from {
+                    new_selection.from = selections[start_idx].from.clone();
+                }
+            }
+            MovementDirection::TowardsEnd => {
+                if selections[end_idx].to > new_selection.to {
+                    new_selection.to = selections[end_idx].to.clone();
+                }
             }
-        } else if selections[end_idx].to > new_selection.to {
-            new_selection.to = selections[end_idx].to.clone();
         }
     }
+}
 
-    /// New index for a selection after rotation depends on movement direction.
-    /// As described in [fix_step_over_rotation] docs, movement towards buffer
-    /// ends requires index decrement.
-    fn get_final_new_index(&self, idx: SelectionIndex) -> SelectionIndex {
-        if self.is_towards_beginning() {
-            idx
-        } else {
-            idx - 1
-        }
+// Built-in motions build new states from old ones, so payloads are cloned as
+// old states are kept in deltas.
+impl<T: Clone> SelectionStorage<T> {
+    /// Moves a selection identified by `id` (see [SelectionKey]) on `n` columns
+    /// left. Will be narrowed to length of 1 character if `extend` is
+    /// `false`.
+    pub fn move_left_single<'k>(
+        &mut self,
+        line_lengths: &impl LineLength,
+        id: impl Into<SelectionKey<'k>>,
+        n: usize,
+        extend: bool,
+    ) -> SelectionDeltas<'_, T> {
+        self.move_n(line_lengths, id.into(), n, extend, |s| {
+            s.move_left(line_lengths, n, extend)
+        })
     }
-}
 
-impl SelectionStorage {
-    /// Moves a selection identified by `id` (its `from` component) on `n`
-    /// columns left. Will be narrowed to length of 1 character if `extend`
-    /// is `false`.
-    pub fn move_left_single(
+    /// Moves a selection identified by `id` (see [SelectionKey]) on `n` columns
+    /// right. Will be narrowed to length of 1 character if `extend` is
+    /// `false`.
+    pub fn move_right_single<'k>(
         &mut self,
         line_lengths: &impl LineLength,
-        id: &Position,
+        id: impl Into<SelectionKey<'k>>,
         n: usize,
         extend: bool,
-    ) -> SelectionDeltas {
-        self.move_common(MovementDirection::Left, line_lengths, id, n, extend)
+    ) -> SelectionDeltas<'_, T> {
+        self.move_n(line_lengths, id.into(), n, extend, |s| {
+            s.move_right(line_lengths, n, extend)
+        })
     }
 
-    /// Moves a selection identified by `id` (its `from` component) on `n`
-    /// columns right. Will be narrowed to length of 1 character if `extend`
-    /// is `false`.
-    pub fn move_right_single(
+    /// Moves a selection identified by `id` (see [SelectionKey]) on `n` lines
+    /// up. Will be narrowed to length of 1 character if `extend` is
+    /// `false`.
+    pub fn move_up_single<'k>(
         &mut self,
         line_lengths: &impl LineLength,
-        id: &Position,
+        id: impl Into<SelectionKey<'k>>,
         n: usize,
         extend: bool,
-    ) -> SelectionDeltas {
-        self.move_common(MovementDirection::Right, line_lengths, id, n, extend)
+    ) -> SelectionDeltas<'_, T> {
+        self.move_n(line_lengths, id.into(), n, extend, |s| {
+            s.move_up(line_lengths, n, extend)
+        })
     }
 
-    /// Moves a selection identified by `id` (its `from` component) on `n`
-    /// lines up. Will be narrowed to length of 1 character if `extend`
-    /// is `false`.
-    pub fn move_up_single(
+    /// Moves a selection identified by `id` (see [SelectionKey]) on `n` lines
+    /// down. Will be narrowed to length of 1 character if `extend` is
+    /// `false`.
+    pub fn move_down_single<'k>(
         &mut self,
         line_lengths: &impl LineLength,
-        id: &Position,
+        id: impl Into<SelectionKey<'k>>,
         n: usize,
         extend: bool,
-    ) -> SelectionDeltas {
-        self.move_common(MovementDirection::Up, line_lengths, id, n, extend)
+    ) -> SelectionDeltas<'_, T> {
+        self.move_n(line_lengths, id.into(), n, extend, |s| {
+            s.move_down(line_lengths, n, extend)
+        })
     }
 
-    /// Moves a selection identified by `id` (its `from` component) on `n`
-    /// lines down. Will be narrowed to length of 1 character if `extend`
+    /// Moves a selection identified by `id` (see [SelectionKey]) to the
+    /// beginning of `n`-th next subword (`parse|Http|Request`,
+    /// `snake|case`). Will be narrowed to length of 1 character if `extend`
     /// is `false`.
-    pub fn move_down_single(
+    pub fn move_subword_forward_single<'k>(
         &mut self,
-        line_lengths: &impl LineLength,
-        id: &Position,
+        text: &impl LineText,
+        id: impl Into<SelectionKey<'k>>,
+        n: usize,
+        extend: bool,
+    ) -> SelectionDeltas<'_, T> {
+        self.move_n(text, id.into(), n, extend, |s| {
+            s.move_subword_forward(text, n, extend)
+        })
+    }
+
+    /// Moves a selection identified by `id` (see [SelectionKey]) to the
+    /// beginning of `n`-th previous subword. Will be narrowed to length of 1
+    /// character if `extend` is `false`.
+    pub fn move_subword_backward_single<'k>(
+        &mut self,
+        text: &impl LineText,
+        id: impl Into<SelectionKey<'k>>,
+        n: usize,
+        extend: bool,
+    ) -> SelectionDeltas<'_, T> {
+        self.move_n(text, id.into(), n, extend, |s| {
+            s.move_subword_backward(text, n, extend)
+        })
+    }
+
+    /// Moves a selection identified by `id` (see [SelectionKey]) to the end
+    /// of `n`-th next subword. Will be narrowed to length of 1 character if
+    /// `extend` is `false`.
+    pub fn move_subword_end_single<'k>(
+        &mut self,
+        text: &impl LineText,
+        id: impl Into<SelectionKey<'k>>,
+        n: usize,
+        extend: bool,
+    ) -> SelectionDeltas<'_, T> {
+        self.move_n(text, id.into(), n, extend, |s| {
+            s.move_subword_end(text, n, extend)
+        })
+    }
+
+    /// Moves all selections to the beginning of `n`-th next subword, see
+    /// [Self::move_subword_forward_single]. Collisions are resolved according
+    /// to the storage's [CollisionConfig].
+    pub fn move_subword_forward_all(
+        &mut self,
+        text: &impl LineText,
         n: usize,
         extend: bool,
-    ) -> SelectionDeltas {
-        self.move_common(MovementDirection::Down, line_lengths, id, n, extend)
+    ) -> SelectionDeltas<'_, T> {
+        self.map_all(text, |s| s.move_subword_forward(text, n, extend))
     }
 
-    /// Common logic for movement.
-    fn move_common(
+    /// Moves all selections to the beginning of `n`-th previous subword, see
+    /// [Self::move_subword_backward_single]. Collisions are resolved according
+    /// to the storage's [CollisionConfig].
+    pub fn move_subword_backward_all(
+        &mut self,
+        text: &impl LineText,
+        n: usize,
+        extend: bool,
+    ) -> SelectionDeltas<'_, T> {
+        self.map_all(text, |s| s.move_subword_backward(text, n, extend))
+    }
+
+    /// Moves all selections to the end of `n`-th next subword, see
+    /// [Self::move_subword_end_single]. Collisions are resolved according to
+    /// the storage's [CollisionConfig].
+    pub fn move_subword_end_all(
+        &mut self,
+        text: &impl LineText,
+        n: usize,
+        extend: bool,
+    ) -> SelectionDeltas<'_, T> {
+        self.map_all(text, |s| s.move_subword_end(text, n, extend))
+    }
+
+    /// Moves a selection identified by `id` (see [SelectionKey]) to the
+    /// beginning of `n`-th next sentence, a blank line or the buffer end. Will
+    /// be narrowed to length of 1 character if `extend` is `false`.
+    pub fn move_sentence_forward_single<'k>(
+        &mut self,
+        text: &impl LineText,
+        id: impl Into<SelectionKey<'k>>,
+        n: usize,
+        extend: bool,
+    ) -> SelectionDeltas<'_, T> {
+        self.move_n(text, id.into(), n, extend, |s| {
+            let stops = sentence_stops_around(text, s.cursor(), n, true);
+            s.move_sentence_forward(&stops, n, extend)
+        })
+    }
+
+    /// Moves a selection identified by `id` (see [SelectionKey]) to the
+    /// beginning of `n`-th previous sentence, a blank line or the buffer
+    /// beginning. Will be narrowed to length of 1 character if `extend` is
+    /// `false`.
+    pub fn move_sentence_backward_single<'k>(
+        &mut self,
+        text: &impl LineText,
+        id: impl Into<SelectionKey<'k>>,
+        n: usize,
+        extend: bool,
+    ) -> SelectionDeltas<'_, T> {
+        self.move_n(text, id.into(), n, extend, |s| {
+            let stops = sentence_stops_around(text, s.cursor(), n, false);
+            s.move_sentence_backward(&stops, n, extend)
+        })
+    }
+
+    /// Moves all selections to the beginning of `n`-th next sentence, see
+    /// [Self::move_sentence_forward_single]. Collisions are resolved according
+    /// to the storage's [CollisionConfig].
+    pub fn move_sentence_forward_all(
+        &mut self,
+        text: &impl LineText,
+        n: usize,
+        extend: bool,
+    ) -> SelectionDeltas<'_, T> {
+        let stops = BufferText::new(text).sentence_stops();
+        self.map_all(text, |s| s.move_sentence_forward(&stops, n, extend))
+    }
+
+    /// Moves all selections to the beginning of `n`-th previous sentence, see
+    /// [Self::move_sentence_backward_single]. Collisions are resolved according
+    /// to the storage's [CollisionConfig].
+    pub fn move_sentence_backward_all(
+        &mut self,
+        text: &impl LineText,
+        n: usize,
+        extend: bool,
+    ) -> SelectionDeltas<'_, T> {
+        let stops = BufferText::new(text).sentence_stops();
+        self.map_all(text, |s| s.move_sentence_backward(&stops, n, extend))
+    }
+}
+
+impl<T> SelectionStorage<T> {
+    /// Moves a selection identified by `id` (see [SelectionKey]) to a state
+    /// provided by `motion`, which is a custom motion, resolving collisions
+    /// according to provided `config` just like built-in `*_single`
+    /// movements do with the storage's [CollisionConfig]. `extend` tells if
+    /// the motion keeps selection's anchor, in that case overlapped
+    /// selections become a part of the moved one on merge.
+    pub fn move_with_config<'k>(
+        &mut self,
+        line_lengths: &impl LineLength,
+        id: impl Into<SelectionKey<'k>>,
+        extend: bool,
+        config: CollisionConfig,
+        motion: impl FnOnce(&Selection<T>) -> Selection<T>,
+    ) -> SelectionDeltas<'_, T> {
+        let id = id.into();
+        self.tracked(|storage| storage.move_with(line_lengths, id, extend, config, motion))
+    }
+
+    /// Common logic for built-in movements on `n` steps with the storage's
+    /// collision settings.
+    fn move_n(
         &mut self,
-        direction: MovementDirection,
         line_lengths: &impl LineLength,
-        id: &Position,
+        id: SelectionKey,
         n: usize,
         extend: bool,
-    ) -> SelectionDeltas {
+        move_fn: impl FnOnce(&Selection<T>) -> Selection<T>,
+    ) -> SelectionDeltas<'_, T> {
         if n == 0 {
             return Default::default();
         }
+        let config = self.collisions;
+        self.tracked(|storage| storage.move_with(line_lengths, id, extend, config, move_fn))
+    }
 
-        let Some(idx_old) = self.find_index_by_id(id.into()) else {
-	    return Default::default();
-	};
+    /// Common logic for movement of a single selection, possibly extending
+    /// it, new selection state is provided by `move_fn`. Collisions are
+    /// resolved according to `config`.
+    fn move_with(
+        &mut self,
+        line_lengths: &impl LineLength,
+        id: SelectionKey,
+        extend: bool,
+        config: CollisionConfig,
+        move_fn: impl FnOnce(&Selection<T>) -> Selection<T>,
+    ) -> SelectionDeltas<'_, T> {
+        let Some(idx_old) = self.find_index_by_key(id) else {
+            return Default::default();
+        };
 
-        let mut selection_new =
-            direction.new_selection_state(&self.selections[idx_old], line_lengths, n, extend);
+        // Moved selection keeps its id whatever state the motion builds:
+        let mut selection_new = Selection {
+            id: self.selections[idx_old].id,
+            ..move_fn(&self.selections[idx_old])
+        };
+        let direction = MovementDirection::of(&self.selections[idx_old], &selection_new);
 
-        // Find new insertion index or possible overlaps.
-        let idx_new = self.find_overlapping_indicies_exlude(
-            (&selection_new.from).into(),
-            (&selection_new.to).into(),
-            idx_old,
+        // Find new insertion index or possible collisions.
+        let mut idx_new = self.find_colliding_indicies(
+            line_lengths,
+            &selection_new,
+            config.merge_touching,
+            Some(idx_old),
         );
 
+        // Trimmed selection won't collide with others, but insertion index is still
+        // required.
+        if let (CollisionPolicy::TrimToFit, Ok(range)) = (config.policy, &idx_new) {
+            let Some(trimmed) = self.trim_to_fit(line_lengths, selection_new, range.clone()) else {
+                return Default::default();
+            };
+            selection_new = trimmed;
+            idx_new = self.find_overlapping_indicies_exlude(
+                (&selection_new.from).into(),
+                (&selection_new.to).into(),
+                idx_old,
+            );
+        }
+
         let deltas = match idx_new {
             Err(idx) if idx == idx_old => {
                 // Updated selection stays at the same index and no collisions to solve:
                 let selection_old = mem::replace(&mut self.selections[idx_old], selection_new);
+                self.remember_id(idx);
                 let mut deltas = SelectionDeltas::default();
-                deltas.push_updated(selection_old, &self.selections[idx]);
+                deltas.push_updated(selection_old, idx_old, &self.selections[idx], idx);
                 deltas
             }
             Err(idx) => {
                 // Replace old selection with a new one and do rotation to fix vector order.
                 let selection_old = mem::replace(&mut self.selections[idx_old], selection_new);
-                direction.fix_step_over_rotation(&mut self.selections, idx_old, idx);
+                // Selection stepped over others, so rotation restores the vector order:
+                let idx_final = if idx < idx_old {
+                    self.selections[idx..=idx_old].rotate_right(1);
+                    idx
+                } else {
+                    // Insertion position is right after the last selection stepped over:
+                    self.selections[idx_old..idx].rotate_left(1);
+                    idx - 1
+                };
+                self.remember_id(idx_final);
                 let mut deltas = SelectionDeltas::default();
                 deltas.push_updated(
                     selection_old,
-                    &self.selections[direction.get_final_new_index(idx)],
+                    idx_old,
+                    &self.selections[idx_final],
+                    idx_final,
                 );
                 deltas
             }
-            Ok(range) => {
-                // Collisions found, overwritten selections will be removed.
-                let start_idx = *range.start();
-                let end_idx = *range.end();
+            Ok(_)
+                if matches!(
+                    config.policy,
+                    CollisionPolicy::RejectNew | CollisionPolicy::TrimToFit
+                ) =>
+            {
+                // Movement is cancelled.
+                Default::default()
+            }
+            Ok(_) if config.policy == CollisionPolicy::KeepExisting => {
+                // Moved selection is dropped in favor of existing ones.
+                let selection_old = self.selections.remove(idx_old);
+                self.forget_id(&selection_old);
+                self.fix_primary(idx_old);
                 let mut deltas = SelectionDeltas::default();
+                deltas.push_deleted(selection_old, idx_old);
+                deltas
+            }
+            Ok(_) => {
+                // Moved selection is taken out first, so collisions are searched among
+                // others only and the range found is where the new state goes:
+                let selection_old = self.selections.remove(idx_old);
+                let Ok(range) = self.find_colliding_indicies(
+                    line_lengths,
+                    &selection_new,
+                    config.merge_touching,
+                    None,
+                ) else {
+                    unreachable!("moved selection collides with others");
+                };
+                // Indices before the moved selection was taken out:
+                let old_idx = |idx: usize| if idx < idx_old { idx } else { idx + 1 };
 
-                // With `extend` flag leftmost/rightmost overlapped selection will become a part
-                // of new selection.
-                if extend {
-                    direction.fix_extend_overlap(&self.selections, range, &mut selection_new);
+                // With `extend` flag leftmost/rightmost overlapped selection will become a
+                // part of new selection.
+                if config.policy == CollisionPolicy::Merge && extend {
+                    direction.fix_extend_overlap(
+                        &self.selections,
+                        range.clone(),
+                        &mut selection_new,
+                    );
                 }
 
-                // Update a selection leaving it at the same place, as others will
-                // be removed anyway.
-                let selection_old = mem::replace(&mut self.selections[idx_old], selection_new);
-                for s in self.selections.drain(start_idx..=end_idx) {
-                    deltas.push_deleted(s);
+                // Overwritten selections are replaced with the new one:
+                let into = selection_new.id;
+                let idx_final = *range.start();
+                let removed: Vec<Selection<T>> = self
+                    .selections
+                    .splice(range.clone(), [selection_new])
+                    .collect();
+                let mut deltas = SelectionDeltas::default();
+                for (idx, s) in range.zip(removed) {
+                    self.forget_id(&s);
+                    if config.policy == CollisionPolicy::Merge {
+                        self.merge_payload(idx_final, &s);
+                    }
+                    deltas.push_merged(s, old_idx(idx), into);
                 }
-                // After deletion of overlapped selections new selection will be placed at the
-                // leftmost index of all involved, so [cmp::min] is legal there.
+                self.fix_primary(idx_final);
+                self.remember_id(idx_final);
                 deltas.push_updated(
                     selection_old,
-                    &self.selections[cmp::min(start_idx, idx_old)],
+                    idx_old,
+                    &self.selections[idx_final],
+                    idx_final,
                 );
                 deltas
             }
@@ -0,0 +1,65 @@
+//! Selection of text objects in [SelectionStorage].
+
+use super::SelectionStorage;
+use crate::{LineText, Selection, SelectionDeltas, TextObject};
+
+impl<T: Clone> SelectionStorage<T> {
+    /// Replaces each selection with `object` (its inner or `around` version)
+    /// found around the selection's cursor. A selection already covering the
+    /// object is extended instead, see [TextObject] for details. Selections
+    /// with no object around are left as is, overlapping results are resolved
+    /// according to the storage's [CollisionConfig](crate::CollisionConfig).
+    pub fn select_text_object(
+        &mut self,
+        text: &impl LineText,
+        object: TextObject,
+        around: bool,
+    ) -> SelectionDeltas<'_, T> {
+        self.map_all(text, |s| match object.find(text, s, around) {
+            Some((from, to)) => Selection {
+                from,
+                to,
+                ..s.clone()
+            },
+            None => s.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_utils::{selections_test, TestText},
+        TextObject,
+    };
+
+    #[test]
+    fn select_text_object() {
+        let text = TestText::new("(foo bar) baz\n(qux)");
+        selections_test! {
+            [
+                (0, 1) - (0, 1),
+                (0, 5) - (0, 5),
+                (0, 11) - (0, 11),
+                (1, 4) - (1, 4),
+            ],
+            storage -> { storage.select_text_object(&text, TextObject::Brackets('(', ')'), false) },
+            [
+                Updated {
+                    old: (0, 1) - (0, 1),
+                    new: (0, 1) - (0, 7),
+                },
+                Deleted((0, 5) - (0, 5)),
+                Updated {
+                    old: (1, 4) - (1, 4),
+                    new: (1, 1) - (1, 3),
+                },
+            ],
+            [
+                (0, 1) - (0, 7),
+                (0, 11) - (0, 11),
+                (1, 1) - (1, 3),
+            ]
+        };
+    }
+}
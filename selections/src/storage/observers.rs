@@ -0,0 +1,157 @@
+//! Subscription on [SelectionStorage] changes.
+
+use super::SelectionStorage;
+use crate::{OwnedSelectionDeltas, Selection};
+
+/// Identifier of a registered observer, see [SelectionStorage::subscribe].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObserverId(usize);
+
+/// Callback to be invoked on storage changes.
+type Observer<T> = Box<dyn FnMut(&OwnedSelectionDeltas<T>) + Send>;
+
+/// Registry of observers.
+pub(crate) struct Observers<T> {
+    observers: Vec<(ObserverId, Observer<T>)>,
+    next_id: usize,
+}
+
+impl<T> Default for Observers<T> {
+    fn default() -> Self {
+        Observers {
+            observers: Vec::new(),
+            next_id: 0,
+        }
+    }
+}
+
+impl<T> Observers<T> {
+    /// Checks if there are no observers.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+
+    /// Invokes all observers with `deltas`.
+    pub(crate) fn notify(&mut self, deltas: &OwnedSelectionDeltas<T>) {
+        for (_, observer) in self.observers.iter_mut() {
+            observer(deltas);
+        }
+    }
+}
+
+impl<T: Clone> SelectionStorage<T> {
+    /// Registers a callback to be invoked with deltas after each operation
+    /// that changed the storage. During a transaction callbacks are not
+    /// invoked until the outermost transaction is committed, and then they
+    /// receive net deltas of the whole transaction.
+    pub fn subscribe(
+        &mut self,
+        observer: impl FnMut(&OwnedSelectionDeltas<T>) + Send + 'static,
+    ) -> ObserverId {
+        let id = ObserverId(self.observers.next_id);
+        self.observers.next_id += 1;
+        self.observers.observers.push((id, Box::new(observer)));
+        self.clone_selection = Some(Selection::clone);
+        id
+    }
+}
+
+impl<T> SelectionStorage<T> {
+    /// Removes an observer, returns `false` if there is no such observer.
+    pub fn unsubscribe(&mut self, id: ObserverId) -> bool {
+        let len = self.observers.observers.len();
+        self.observers
+            .observers
+            .retain(|(observer_id, _)| *observer_id != id);
+        len != self.observers.observers.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{test_utils::TestLineLengths, OwnedSelectionDelta, Position, Selection};
+
+    fn observed(
+        storage: &mut SelectionStorage,
+    ) -> (ObserverId, Arc<Mutex<Vec<OwnedSelectionDelta>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        let id = storage.subscribe(move |deltas| {
+            received_clone
+                .lock()
+                .unwrap()
+                .extend(deltas.iter().cloned())
+        });
+        (id, received)
+    }
+
+    #[test]
+    fn notified_on_changes() {
+        let mut line_lengths = TestLineLengths::new();
+        line_lengths.set(0, 20);
+        let mut storage = SelectionStorage::new();
+        let (id, received) = observed(&mut storage);
+
+        let deltas_len = storage
+            .insert(Selection::new(Position::new(0, 5), Position::new(0, 6)))
+            .into_iter()
+            .count();
+        assert_eq!(deltas_len, 1);
+        storage.move_right_single(&line_lengths, &Position::new(0, 0), 1, false);
+        // No changes, no notification:
+        storage.move_right_single(&line_lengths, &Position::new(0, 0), 1, false);
+
+        assert_eq!(
+            std::mem::take(&mut *received.lock().unwrap()),
+            [
+                OwnedSelectionDelta::Created(Selection::new(
+                    Position::new(0, 5),
+                    Position::new(0, 6)
+                )),
+                OwnedSelectionDelta::Updated {
+                    old: Selection::new(Position::new(0, 0), Position::new(0, 0)),
+                    new: Selection::new(Position::new(0, 1), Position::new(0, 1)),
+                },
+            ]
+        );
+
+        assert!(storage.unsubscribe(id));
+        assert!(!storage.unsubscribe(id));
+        storage.move_right_single(&line_lengths, &Position::new(0, 1), 1, false);
+        assert!(received.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn notified_on_commit() {
+        let mut line_lengths = TestLineLengths::new();
+        line_lengths.set(0, 20);
+        let mut storage = SelectionStorage::new();
+        let (_, received) = observed(&mut storage);
+
+        storage.begin_transaction();
+        storage.move_right_single(&line_lengths, &Position::new(0, 0), 1, false);
+        storage.begin_transaction();
+        storage.move_right_single(&line_lengths, &Position::new(0, 1), 1, false);
+        storage.commit();
+        assert!(received.lock().unwrap().is_empty());
+        storage.commit();
+
+        assert_eq!(
+            std::mem::take(&mut *received.lock().unwrap()),
+            [OwnedSelectionDelta::Updated {
+                old: Selection::new(Position::new(0, 0), Position::new(0, 0)),
+                new: Selection::new(Position::new(0, 2), Position::new(0, 2)),
+            }]
+        );
+
+        storage.begin_transaction();
+        storage.move_right_single(&line_lengths, &Position::new(0, 2), 1, false);
+        storage.rollback();
+        assert!(received.lock().unwrap().is_empty());
+    }
+}
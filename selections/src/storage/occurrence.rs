@@ -0,0 +1,489 @@
+//! Selection of text occurrences in [SelectionStorage]: adding the next
+//! occurrence of the primary selection's text (VS Code's Ctrl-D) and selecting
+//! all occurrences at once.
+
+use std::mem;
+
+use super::{clipboard::selection_text, matches::Matcher, SelectionStorage};
+use crate::{
+    text::{is_word_char, BufferText},
+    LineText, Position, Selection, SelectionDeltas, SelectionId,
+};
+
+/// Step of replacement of selections with occurrences.
+enum OccurrenceStep<T> {
+    /// Previous selection from the index is not an occurrence.
+    Deleted(Selection<T>, usize),
+    /// Occurrence on the index is a new selection.
+    Created(usize),
+}
+
+impl<T: Clone> SelectionStorage<T> {
+    /// Searches forward from the most recently added selection for the next
+    /// occurrence of the primary selection's text not selected yet, wrapping
+    /// around the buffer end, and adds it as a new primary selection carrying
+    /// the same payload.
+    ///
+    /// If the primary selection is a cursor within a word, it's expanded to
+    /// the word instead.
+    pub fn add_next_occurrence(&mut self, text: &impl LineText) -> SelectionDeltas<'_, T> {
+        let Some(primary) = self.primary() else {
+            return SelectionDeltas::new();
+        };
+        if let Some(word) = word_at(text, primary) {
+            let id = primary.id;
+            return self.replace_selection(text, id, word);
+        }
+
+        match self.next_occurrence(text) {
+            Some(occurrence) => self.insert(occurrence),
+            None => SelectionDeltas::new(),
+        }
+    }
+
+    /// Moves the most recently added selection to the next occurrence of the
+    /// primary selection's text, just like [Self::add_next_occurrence] would
+    /// add it, to skip the current occurrence.
+    pub fn skip_occurrence(&mut self, text: &impl LineText) -> SelectionDeltas<'_, T> {
+        let Some(id) = self.newest().and_then(|s| s.id) else {
+            return SelectionDeltas::new();
+        };
+        match self.next_occurrence(text) {
+            Some(occurrence) => self.replace_selection(text, Some(id), occurrence),
+            None => SelectionDeltas::new(),
+        }
+    }
+
+    /// Replaces all selections with selections of `pattern` matches in the
+    /// whole buffer in a single pass, new selections carry the primary
+    /// selection's payload. Selections that match an occurrence exactly are
+    /// kept as is. If nothing matches the storage is left unchanged.
+    ///
+    /// The occurrence at or after the primary selection becomes primary.
+    pub fn select_all_occurrences<M: Matcher + ?Sized>(
+        &mut self,
+        text: &impl LineText,
+        pattern: &M,
+    ) -> SelectionDeltas<'_, T> {
+        self.tracked(|storage| storage.select_all_occurrences_internal(text, pattern))
+    }
+
+    fn select_all_occurrences_internal<M: Matcher + ?Sized>(
+        &mut self,
+        text: &impl LineText,
+        pattern: &M,
+    ) -> SelectionDeltas<'_, T> {
+        let Some((primary_from, payload)) =
+            self.primary().map(|s| (s.from.clone(), s.payload.clone()))
+        else {
+            return SelectionDeltas::new();
+        };
+        let buffer = BufferText::new(text);
+        let occurrences: Vec<Selection<T>> = pattern
+            .find_matches(buffer.as_str())
+            .into_iter()
+            .filter(|range| !range.is_empty())
+            .map(|range| {
+                Selection::with_payload(
+                    buffer.position(range.start),
+                    buffer.position(buffer.last_char_offset(range.end)),
+                    payload.clone(),
+                )
+            })
+            .collect();
+        if occurrences.is_empty() {
+            return SelectionDeltas::new();
+        }
+
+        // Both occurrences and previous selections are sorted, so they're merged in
+        // one pass instead of inserting occurrences one by one:
+        let is_same = |old: &Selection<T>, new: &Selection<T>| {
+            old.from.weak_eq(&new.from) && old.to.weak_eq(&new.to)
+        };
+        let mut old_selections = mem::take(&mut self.selections)
+            .into_iter()
+            .enumerate()
+            .peekable();
+        let mut new_selections = Vec::with_capacity(occurrences.len());
+        let mut steps = Vec::new();
+        for occurrence in occurrences {
+            while let Some((old_idx, old)) = old_selections
+                .next_if(|(_, old)| old.from <= occurrence.from && !is_same(old, &occurrence))
+            {
+                steps.push(OccurrenceStep::Deleted(old, old_idx));
+            }
+            match old_selections.next_if(|(_, old)| is_same(old, &occurrence)) {
+                Some((_, old)) => new_selections.push(old),
+                None => {
+                    steps.push(OccurrenceStep::Created(new_selections.len()));
+                    new_selections.push(self.identify(occurrence));
+                }
+            }
+        }
+        steps.extend(old_selections.map(|(old_idx, old)| OccurrenceStep::Deleted(old, old_idx)));
+        self.selections = new_selections;
+        self.rebuild_ids();
+
+        let idx = self.selections.partition_point(|s| s.from < primary_from);
+        self.set_primary_index(idx.min(self.selections.len() - 1));
+
+        let mut deltas = SelectionDeltas::with_capacity(steps.len());
+        for step in steps {
+            match step {
+                OccurrenceStep::Deleted(old, old_idx) => deltas.push_deleted(old, old_idx),
+                OccurrenceStep::Created(idx) => deltas.push_created(&self.selections[idx], idx),
+            }
+        }
+
+        debug_assert!(self.is_state_correct());
+        deltas
+    }
+
+    /// Finds the next not selected occurrence of the primary selection's text
+    /// after the most recently added selection.
+    fn next_occurrence(&self, text: &impl LineText) -> Option<Selection<T>> {
+        let primary = self.primary()?;
+        let needle = selection_text(text, primary);
+        if needle.is_empty() {
+            return None;
+        }
+
+        let after = &self.newest()?.to;
+        let is_after = |p: &Position| (p.line, p.column) > (after.line, after.column);
+        // Only lines an occurrence starting on a line may span are gathered, so the
+        // search stops on the first occurrence found without reading the whole buffer.
+        // Lines after the newest selection go first, then ones from the buffer
+        // beginning:
+        let span = needle.matches('\n').count() + 1;
+        let not_wrapped = text.lines_count().saturating_sub(after.line);
+        let lines = (after.line..text.lines_count()).chain(0..=after.line);
+        for (step, line) in lines.enumerate() {
+            let wrapped = step >= not_wrapped;
+            let window = BufferText::lines(text, line..line + span);
+            let haystack = window.as_str();
+            let line_end = haystack.find('\n').unwrap_or(haystack.len());
+            let mut search_from = 0;
+            while let Some(idx) = haystack
+                .get(search_from..)
+                .and_then(|rest| rest.find(&needle))
+            {
+                let range = search_from + idx..search_from + idx + needle.len();
+                if range.start > line_end {
+                    break;
+                }
+                let (from, to) = window.range_positions(&range);
+                if wrapped && is_after(&from) {
+                    return None;
+                }
+                if (wrapped || is_after(&from))
+                    && self
+                        .find_overlapping_indicies((&from).into(), (&to).into())
+                        .is_err()
+                {
+                    return Some(Selection::with_payload(from, to, primary.payload.clone()));
+                }
+                search_from = window.next_offset(range.start);
+            }
+        }
+        None
+    }
+
+    /// The most recently added selection, ids are assigned in order.
+    fn newest(&self) -> Option<&Selection<T>> {
+        self.selections.iter().max_by_key(|s| s.id)
+    }
+
+    /// Replaces a selection with `id` with `selection` keeping its id and
+    /// payload.
+    fn replace_selection(
+        &mut self,
+        text: &impl LineText,
+        id: Option<SelectionId>,
+        selection: Selection<T>,
+    ) -> SelectionDeltas<'_, T> {
+        self.map_all(text, |s| {
+            if s.id == id {
+                Selection {
+                    payload: s.payload.clone(),
+                    ..selection.clone()
+                }
+            } else {
+                s.clone()
+            }
+        })
+    }
+}
+
+/// Selection of a word under the cursor if `selection` is a cursor within a
+/// longer word.
+fn word_at<T: Clone>(text: &impl LineText, selection: &Selection<T>) -> Option<Selection<T>> {
+    if !selection.from.weak_eq(&selection.to) {
+        return None;
+    }
+    let line = text.get_line(selection.from.line)?;
+    let chars: Vec<char> = line.chars().collect();
+    let column = selection.from.column;
+    if !chars.get(column).copied().is_some_and(is_word_char) {
+        return None;
+    }
+
+    let start = chars[..column]
+        .iter()
+        .rposition(|ch| !is_word_char(*ch))
+        .map_or(0, |idx| idx + 1);
+    let end = chars[column..]
+        .iter()
+        .position(|ch| !is_word_char(*ch))
+        .map_or(chars.len(), |idx| column + idx);
+    if end - start == 1 {
+        return None;
+    }
+    let mut from = selection.from.clone();
+    from.column = start;
+    let mut to = selection.from.clone();
+    to.column = end - 1;
+
+    Some(Selection::with_payload(
+        from.remove_sticky(),
+        to.remove_sticky(),
+        selection.payload.clone(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        test_utils::{selections_test, TestText},
+        Position,
+    };
+
+    #[test]
+    fn add_next() {
+        let text = TestText::new("foo bar foo\nfoo baz");
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+            ],
+            storage -> {
+                storage.add_next_occurrence(&text);
+                storage.add_next_occurrence(&text)
+            },
+            [
+                Created((1, 0) - (1, 2)),
+            ],
+            [
+                (0, 0) - (0, 2),
+                (0, 8) - (0, 10),
+                (1, 0) - (1, 2),
+            ]
+        };
+    }
+
+    #[test]
+    fn wrap_around_and_stop() {
+        let text = TestText::new("foo bar foo\nfoo baz");
+        selections_test! {
+            [
+                (0, 8) - (0, 10),
+                (1, 0) - (1, 2),
+            ],
+            storage -> {
+                storage.add_next_occurrence(&text);
+                assert_eq!(
+                    storage.primary().map(|s| s.from.clone()),
+                    Some(Position::new(0, 0))
+                );
+                storage.add_next_occurrence(&text)
+            },
+            [],
+            [
+                (0, 0) - (0, 2),
+                (0, 8) - (0, 10),
+                (1, 0) - (1, 2),
+            ]
+        };
+    }
+
+    #[test]
+    fn word_under_cursor() {
+        let text = TestText::new("let foo_1 = foo_1;");
+        selections_test! {
+            [
+                (0, 5) - (0, 5),
+            ],
+            storage -> { storage.add_next_occurrence(&text) },
+            [
+                Updated {
+                    old: (0, 5) - (0, 5),
+                    new: (0, 4) - (0, 8),
+                },
+            ],
+            [
+                (0, 4) - (0, 8),
+            ]
+        };
+
+        let text = TestText::new("foo x foo");
+        let mut storage = SelectionStorage::new();
+        storage.add_next_occurrence(&text);
+        storage.add_next_occurrence(&text);
+        assert_eq!(
+            storage
+                .iter_all()
+                .map(|s| (s.from.clone(), s.to.clone()))
+                .collect::<Vec<_>>(),
+            [
+                (Position::new(0, 0), Position::new(0, 2)),
+                (Position::new(0, 6), Position::new(0, 8))
+            ]
+        );
+    }
+
+    #[test]
+    fn word_under_sticky_cursor() {
+        let text = TestText::new("let foo_1 = foo_1;\nfoo_1");
+        selections_test! {
+            [
+                (1, 2) - (1, 2) sticky 14,
+            ],
+            storage -> {
+                storage.add_next_occurrence(&text);
+                storage.add_next_occurrence(&text)
+            },
+            [
+                Created((0, 4) - (0, 8)),
+            ],
+            [
+                (0, 4) - (0, 8),
+                (1, 0) - (1, 4),
+            ]
+        };
+    }
+
+    #[test]
+    fn select_all() {
+        let text = TestText::new("foo bar foo\nfoo");
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+                (0, 4) - (0, 6),
+                (0, 8) - (0, 9),
+            ],
+            storage -> {
+                storage.set_primary(&Position::new(0, 4));
+                storage.select_all_occurrences(&text, "foo")
+            },
+            [
+                Deleted((0, 4) - (0, 6)),
+                Deleted((0, 8) - (0, 9)),
+                Created((0, 8) - (0, 10)),
+                Created((1, 0) - (1, 2)),
+            ],
+            [
+                (0, 0) - (0, 2),
+                (0, 8) - (0, 10),
+                (1, 0) - (1, 2),
+            ]
+        };
+
+        let mut storage = SelectionStorage::new();
+        assert_eq!(
+            storage.select_all_occurrences(&text, "baz").iter().count(),
+            0
+        );
+    }
+
+    #[test]
+    fn skip() {
+        let text = TestText::new("ab ab ab ab");
+        selections_test! {
+            [
+                (0, 0) - (0, 1),
+            ],
+            storage -> {
+                storage.add_next_occurrence(&text);
+                storage.skip_occurrence(&text)
+            },
+            [
+                Updated {
+                    old: (0, 3) - (0, 4),
+                    new: (0, 6) - (0, 7),
+                },
+            ],
+            [
+                (0, 0) - (0, 1),
+                (0, 6) - (0, 7),
+            ]
+        };
+    }
+
+    #[test]
+    fn skip_moves_newest() {
+        let text = TestText::new("ab ab ab ab");
+        selections_test! {
+            [
+                (0, 0) - (0, 1),
+            ],
+            storage -> {
+                storage.add_next_occurrence(&text);
+                storage.set_primary(&Position::new(0, 0));
+                storage.skip_occurrence(&text)
+            },
+            [
+                Updated {
+                    old: (0, 3) - (0, 4),
+                    new: (0, 6) - (0, 7),
+                },
+            ],
+            [
+                (0, 0) - (0, 1),
+                (0, 6) - (0, 7),
+            ]
+        };
+    }
+
+    #[test]
+    fn single_character() {
+        let text = TestText::new("a + b + c");
+        selections_test! {
+            [
+                (0, 2) - (0, 2),
+            ],
+            storage -> { storage.add_next_occurrence(&text) },
+            [
+                Created((0, 6) - (0, 6)),
+            ],
+            [
+                (0, 2) - (0, 2),
+                (0, 6) - (0, 6),
+            ]
+        };
+    }
+
+    #[test]
+    fn search_after_newest() {
+        let text = TestText::new("ab ab ab ab");
+        selections_test! {
+            [
+                (0, 0) - (0, 1),
+            ],
+            storage -> {
+                storage.add_next_occurrence(&text);
+                storage.skip_occurrence(&text);
+                storage.set_primary(&Position::new(0, 0));
+                storage.add_next_occurrence(&text)
+            },
+            [
+                Created((0, 9) - (0, 10)),
+            ],
+            [
+                (0, 0) - (0, 1),
+                (0, 6) - (0, 7),
+                (0, 9) - (0, 10),
+            ]
+        };
+    }
+}
@@ -0,0 +1,127 @@
+//! Payloads of selections in [SelectionStorage].
+
+use super::{query::SelectionIndex, SelectionStorage};
+use crate::Selection;
+
+/// Callback to merge payload of an absorbed selection into the absorber's one.
+pub(super) type PayloadMerge<T> = Box<dyn FnMut(&mut T, &T) + Send>;
+
+impl<T> SelectionStorage<T> {
+    /// Sets a callback invoked when a selection absorbs another one on merge:
+    /// it receives the absorber's payload to update and the absorbed one's.
+    /// Without the callback absorbed payloads are dropped.
+    pub fn set_payload_merge(&mut self, merge: impl FnMut(&mut T, &T) + Send + 'static) {
+        self.payload_merge = Some(Box::new(merge));
+    }
+
+    /// Merges payload of `absorbed` selection into the one at `idx`.
+    pub(super) fn merge_payload(&mut self, idx: SelectionIndex, absorbed: &Selection<T>) {
+        if let Some(merge) = self.payload_merge.as_mut() {
+            merge(&mut self.selections[idx].payload, &absorbed.payload);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        test_utils::{selection_with_payload, storage_with_payload, TestLineLengths},
+        Position,
+    };
+
+    fn storage() -> SelectionStorage<Vec<&'static str>> {
+        let mut storage = storage_with_payload(
+            vec!["a"],
+            [
+                selection_with_payload((0, 5), (0, 7), vec!["b"]),
+                selection_with_payload((0, 10), (0, 12), vec!["c"]),
+            ],
+        );
+        storage.set_payload_merge(|into, absorbed| into.extend_from_slice(absorbed));
+        storage
+    }
+
+    fn payloads<T: Clone>(storage: &SelectionStorage<T>) -> Vec<T> {
+        storage.iter_all().map(|s| s.payload().clone()).collect()
+    }
+
+    #[test]
+    fn payload_survives_movement() {
+        let line_lengths = TestLineLengths::from_lengths([20, 20]);
+        let mut storage = storage();
+
+        storage.move_down_single(&line_lengths, &Position::new(0, 5), 1, false);
+        assert_eq!(payloads(&storage), [vec!["a"], vec!["c"], vec!["b"]]);
+    }
+
+    #[test]
+    fn merge_on_insert() {
+        let mut storage = storage();
+        storage.insert(selection_with_payload((0, 6), (0, 11), vec!["d"]));
+        assert_eq!(payloads(&storage), [vec!["a"], vec!["d", "b", "c"]]);
+    }
+
+    #[test]
+    fn merge_on_movement() {
+        let line_lengths = TestLineLengths::from_lengths([20]);
+        let mut storage = storage();
+
+        storage.move_right_single(&line_lengths, &Position::new(0, 0), 6, true);
+        assert_eq!(payloads(&storage), [vec!["a", "b"], vec!["c"]]);
+    }
+
+    #[test]
+    fn merge_on_map_all() {
+        let mut storage = storage();
+
+        storage.map_all(&TestLineLengths::new(), |s| {
+            let mut s = s.clone();
+            s.to.column += 5;
+            s
+        });
+        assert_eq!(payloads(&storage), [vec!["a", "b", "c"]]);
+    }
+
+    #[test]
+    fn dropped_without_merge_hook() {
+        let mut storage = SelectionStorage::with_payload(1);
+        storage.insert(selection_with_payload((0, 0), (0, 3), 2));
+        assert_eq!(payloads(&storage), [2]);
+    }
+
+    #[test]
+    fn payload_not_cloneable() {
+        #[derive(Debug, PartialEq)]
+        struct Register(u32);
+
+        let line_lengths = TestLineLengths::from_lengths([20]);
+        let mut storage = SelectionStorage::with_payload(Register(0));
+        storage.set_payload_merge(|into, absorbed| into.0 += absorbed.0);
+        storage.extend_from([Selection::with_payload(
+            Position::new(0, 5),
+            Position::new(0, 7),
+            Register(1),
+        )]);
+        storage.insert(Selection::with_payload(
+            Position::new(0, 6),
+            Position::new(0, 9),
+            Register(2),
+        ));
+        storage.move_with_config(
+            &line_lengths,
+            &Position::new(0, 0),
+            false,
+            Default::default(),
+            |_| Selection::with_payload(Position::new(0, 12), Position::new(0, 12), Register(4)),
+        );
+        storage.map_all(&line_lengths, |s| {
+            Selection::with_payload(s.from.clone(), s.to.clone(), Register(s.payload.0 * 10))
+        });
+
+        let payloads: Vec<_> = storage.iter_all().map(Selection::payload).collect();
+        assert_eq!(payloads, [&Register(30), &Register(40)]);
+    }
+}
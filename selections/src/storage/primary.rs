@@ -0,0 +1,118 @@
+//! Primary selection of [SelectionStorage].
+
+use super::{query::SelectionIndex, SelectionKey, SelectionStorage};
+use crate::Selection;
+
+impl<T> SelectionStorage<T> {
+    /// Returns primary selection, it's the one inserted last unless changed
+    /// with [Self::set_primary]. Primary selection moved or merged with others
+    /// remains primary.
+    pub fn primary(&self) -> Option<&Selection<T>> {
+        self.primary_index().map(|idx| &self.selections[idx])
+    }
+
+    /// Makes a selection identified by `id` (see [SelectionKey]) primary.
+    /// Returns `false` if there is no such selection.
+    pub fn set_primary<'k>(&mut self, id: impl Into<SelectionKey<'k>>) -> bool {
+        match self.find_index_by_key(id.into()) {
+            Some(idx) => {
+                self.primary = self.selections[idx].id;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Checks if the selection is the primary one.
+    pub fn is_primary(&self, selection: &Selection<T>) -> bool {
+        selection.id.is_some() && selection.id == self.primary
+    }
+
+    /// Returns index of the primary selection.
+    pub(crate) fn primary_index(&self) -> Option<SelectionIndex> {
+        self.primary
+            .and_then(|id| self.find_index_by_key(SelectionKey::Id(id)))
+    }
+
+    /// Makes a selection on `idx` primary.
+    pub(crate) fn set_primary_index(&mut self, idx: SelectionIndex) {
+        self.primary = self.selections[idx].id;
+    }
+
+    /// If primary selection is gone after a change, makes a selection on `idx`
+    /// primary instead, or the closest one if `idx` is out of bounds.
+    pub(crate) fn fix_primary(&mut self, idx: SelectionIndex) {
+        if self.primary_index().is_none() {
+            self.primary = self
+                .selections
+                .get(idx)
+                .or_else(|| self.selections.last())
+                .and_then(|s| s.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{test_utils::TestLineLengths, Position};
+
+    #[test]
+    fn inserted_is_primary() {
+        let mut storage = SelectionStorage::new();
+        assert_eq!(storage.primary(), Some(&Selection::default()));
+
+        storage.insert(Selection::new(Position::new(1, 0), Position::new(1, 3)));
+        storage.insert(Selection::new(Position::new(0, 3), Position::new(0, 5)));
+        assert_eq!(
+            storage.primary(),
+            Some(&Selection::new(Position::new(0, 3), Position::new(0, 5)))
+        );
+
+        assert!(storage.set_primary(&Position::new(1, 0)));
+        assert!(!storage.set_primary(&Position::new(1, 1)));
+        assert_eq!(
+            storage.primary(),
+            Some(&Selection::new(Position::new(1, 0), Position::new(1, 3)))
+        );
+    }
+
+    #[test]
+    fn moved_remains_primary() {
+        let mut line_lengths = TestLineLengths::new();
+        line_lengths.set(0, 10);
+        line_lengths.set(1, 10);
+        let mut storage = SelectionStorage::new();
+        storage.insert(Selection::new(Position::new(1, 5), Position::new(1, 7)));
+        storage.set_primary(&Position::new(0, 0));
+
+        storage.move_down_single(&line_lengths, &Position::new(0, 0), 1, false);
+        assert_eq!(
+            storage.primary(),
+            Some(&Selection::new(Position::new(1, 0), Position::new(1, 0)))
+        );
+
+        // Primary absorbs another selection:
+        storage.move_right_single(&line_lengths, &Position::new(1, 0), 5, false);
+        assert_eq!(
+            storage.primary(),
+            Some(&Selection::new(Position::new(1, 5), Position::new(1, 5)))
+        );
+    }
+
+    #[test]
+    fn absorbed_primary() {
+        let mut line_lengths = TestLineLengths::new();
+        line_lengths.set(0, 10);
+        let mut storage = SelectionStorage::new();
+        storage.insert(Selection::new(Position::new(0, 3), Position::new(0, 5)));
+
+        storage.move_right_single(&line_lengths, &Position::new(0, 0), 4, false);
+        assert_eq!(
+            storage.primary(),
+            Some(&Selection::new(Position::new(0, 4), Position::new(0, 4)))
+        );
+    }
+}
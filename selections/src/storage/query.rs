@@ -31,7 +31,7 @@ impl<'a> From<Position> for PositionQuery<'a> {
     }
 }
 
-impl SelectionStorage {
+impl<T> SelectionStorage<T> {
     /// Find a Selection by its `from` component.
     pub(crate) fn find_index_by_id(&self, from: PositionQuery) -> Option<SelectionIndex> {
         self.selections
@@ -131,6 +131,7 @@ mod tests {
                 Selection::new(Position::new(2, 20), Position::new(4, 10)),
                 Selection::new(Position::new(4, 20), Position::new(7, 11)),
             ],
+            ..Default::default()
         };
 
         // Overlap on the right side:
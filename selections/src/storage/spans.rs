@@ -0,0 +1,247 @@
+//! Per-line view of selections for renderers.
+
+use std::ops::Range;
+
+use super::{get::SelectionsIter, SelectionStorage};
+use crate::{LineLength, Selection};
+
+/// Part of a line covered by a selection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineSpan {
+    /// First covered column.
+    pub start: usize,
+    /// Column after the last covered one, not including line end.
+    pub end: usize,
+    /// Whether line end (newline) is covered too.
+    pub newline: bool,
+    /// Whether the span belongs to the primary selection.
+    pub primary: bool,
+}
+
+/// Cursor of a selection on a line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineCaret {
+    /// Column of the cursor, it equals to the line length if the cursor is on
+    /// the line end.
+    pub column: usize,
+    /// Whether the cursor belongs to the primary selection.
+    pub primary: bool,
+}
+
+/// Selections of a line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineHighlights {
+    /// Line index.
+    pub line: usize,
+    /// Covered parts of the line ordered by columns.
+    pub spans: Vec<LineSpan>,
+    /// Cursors on the line ordered by columns.
+    pub carets: Vec<LineCaret>,
+}
+
+/// Iterator over lines with their selections, see
+/// [SelectionStorage::iter_line_spans].
+pub struct LineSpans<'a, L, T = ()> {
+    storage: &'a SelectionStorage<T>,
+    line_lengths: &'a L,
+    lines: Range<usize>,
+    selections: SelectionsIter<'a, T>,
+}
+
+impl<L: LineLength, T> Iterator for LineSpans<'_, L, T> {
+    type Item = LineHighlights;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = self.lines.next()?;
+        let line_length = self.line_lengths.get_len(line)?;
+
+        // Selections that ended on previous lines are not needed anymore:
+        while let Some(s) = self.selections.as_slice().first() {
+            if s.to.line < line {
+                self.selections.next();
+            } else {
+                break;
+            }
+        }
+
+        let mut highlights = LineHighlights {
+            line,
+            spans: Vec::new(),
+            carets: Vec::new(),
+        };
+        // Remaining selections are not consumed as they could continue on next lines:
+        for s in self
+            .selections
+            .as_slice()
+            .iter()
+            .take_while(|s| s.from.line <= line)
+        {
+            let primary = self.storage.is_primary(s);
+            highlights
+                .spans
+                .push(line_span(s, line, line_length, primary));
+            if s.cursor().line == line {
+                highlights.carets.push(LineCaret {
+                    column: s.cursor().column,
+                    primary,
+                });
+            }
+        }
+
+        Some(highlights)
+    }
+}
+
+/// Part of the `line` covered by the `selection` which is known to overlap
+/// with it.
+pub(super) fn line_span<T>(
+    selection: &Selection<T>,
+    line: usize,
+    line_length: usize,
+    primary: bool,
+) -> LineSpan {
+    let start = if selection.from.line == line {
+        selection.from.column
+    } else {
+        0
+    };
+    let (end, newline) = if selection.to.line == line {
+        if selection.to.column >= line_length {
+            (line_length, true)
+        } else {
+            (selection.to.column + 1, false)
+        }
+    } else {
+        (line_length, true)
+    };
+
+    LineSpan {
+        start,
+        end,
+        newline,
+        primary,
+    }
+}
+
+impl<T> SelectionStorage<T> {
+    /// Returns iterator over each line of `lines` range with parts covered by
+    /// selections and cursors on it. Lines out of buffer are not yielded.
+    pub fn iter_line_spans<'a, L: LineLength>(
+        &'a self,
+        line_lengths: &'a L,
+        lines: Range<usize>,
+    ) -> LineSpans<'a, L, T> {
+        LineSpans {
+            storage: self,
+            line_lengths,
+            selections: self.iter_from_line(lines.start),
+            lines,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{test_utils::TestLineLengths, Position};
+
+    fn span(start: usize, end: usize, newline: bool, primary: bool) -> LineSpan {
+        LineSpan {
+            start,
+            end,
+            newline,
+            primary,
+        }
+    }
+
+    fn caret(column: usize, primary: bool) -> LineCaret {
+        LineCaret { column, primary }
+    }
+
+    #[test]
+    fn iter_line_spans() {
+        let mut line_lengths = TestLineLengths::new();
+        line_lengths.set(0, 10);
+        line_lengths.set(1, 10);
+        line_lengths.set(2, 0);
+        line_lengths.set(3, 10);
+        line_lengths.set(4, 10);
+
+        let mut storage = SelectionStorage::new();
+        storage.insert(Selection::new(Position::new(0, 3), Position::new(0, 5)));
+        storage.insert(Selection::new(Position::new(3, 2), Position::new(0, 8)));
+        storage.insert(Selection::new(Position::new(3, 4), Position::new(3, 10)));
+        storage.set_primary(&Position::new(0, 8));
+
+        let mut iter = storage.iter_line_spans(&line_lengths, 0..10);
+        assert_eq!(
+            iter.next(),
+            Some(LineHighlights {
+                line: 0,
+                spans: vec![
+                    span(0, 1, false, false),
+                    span(3, 6, false, false),
+                    span(8, 10, true, true),
+                ],
+                carets: vec![caret(0, false), caret(5, false), caret(8, true)],
+            })
+        );
+        assert_eq!(
+            iter.next(),
+            Some(LineHighlights {
+                line: 1,
+                spans: vec![span(0, 10, true, true)],
+                carets: vec![],
+            })
+        );
+        assert_eq!(
+            iter.next(),
+            Some(LineHighlights {
+                line: 2,
+                spans: vec![span(0, 0, true, true)],
+                carets: vec![],
+            })
+        );
+        assert_eq!(
+            iter.next(),
+            Some(LineHighlights {
+                line: 3,
+                spans: vec![span(0, 3, false, true), span(4, 10, true, false)],
+                carets: vec![caret(10, false)],
+            })
+        );
+        assert_eq!(
+            iter.next(),
+            Some(LineHighlights {
+                line: 4,
+                spans: vec![],
+                carets: vec![],
+            })
+        );
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn iter_line_spans_from_the_middle() {
+        let mut line_lengths = TestLineLengths::new();
+        line_lengths.set(0, 10);
+        line_lengths.set(1, 10);
+        line_lengths.set(2, 10);
+
+        let mut storage = SelectionStorage::new();
+        storage.insert(Selection::new(Position::new(0, 3), Position::new(2, 5)));
+
+        let mut iter = storage.iter_line_spans(&line_lengths, 1..2);
+        assert_eq!(
+            iter.next(),
+            Some(LineHighlights {
+                line: 1,
+                spans: vec![span(0, 10, true, true)],
+                carets: vec![],
+            })
+        );
+        assert_eq!(iter.next(), None);
+    }
+}
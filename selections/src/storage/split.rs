@@ -0,0 +1,301 @@
+//! Splitting of [SelectionStorage] selections on a separator.
+
+use std::{mem, ops::Range};
+
+use super::{
+    clipboard::selection_text,
+    matches::{sub_selections, Matcher},
+    SelectionStorage,
+};
+use crate::{LineText, SelectionDeltas};
+
+impl<T: Clone> SelectionStorage<T> {
+    /// Splits each selection on every `separator` match, pieces between
+    /// matches become selections carrying the payload of their selection.
+    /// Empty pieces are dropped unless `keep_empty` is set, in which case
+    /// they become single character selections on the following separator
+    /// (or on the last one for a trailing piece). If no pieces remain at all
+    /// the storage is left unchanged.
+    ///
+    /// Split selections are reported as `Deleted` and pieces are `Created`,
+    /// selections without separators or with a single piece covering the
+    /// whole selection are left as is. The first piece of the
+    /// primary selection becomes primary.
+    pub fn split_selections<M: Matcher + ?Sized>(
+        &mut self,
+        text: &impl LineText,
+        separator: &M,
+        keep_empty: bool,
+    ) -> SelectionDeltas<'_, T> {
+        self.tracked(|storage| storage.split_selections_internal(text, separator, keep_empty))
+    }
+
+    fn split_selections_internal<M: Matcher + ?Sized>(
+        &mut self,
+        text: &impl LineText,
+        separator: &M,
+        keep_empty: bool,
+    ) -> SelectionDeltas<'_, T> {
+        // Pieces of every selection which actually changes, in storage order:
+        let mut splits = Vec::new();
+        let mut primary_split = None;
+        let mut remaining = self.selections.len();
+        for (idx, selection) in self.selections.iter().enumerate() {
+            let haystack = selection_text(text, selection);
+            let mut separators = separator.find_matches(&haystack);
+            separators.retain(|s| !s.is_empty());
+            if separators.is_empty() {
+                continue;
+            }
+
+            let ranges = piece_ranges(&haystack, separators, keep_empty);
+            let pieces: Vec<_> = sub_selections(selection, &haystack, ranges).collect();
+            if let [piece] = pieces.as_slice() {
+                if piece.from.weak_eq(&selection.from) && piece.to.weak_eq(&selection.to) {
+                    continue;
+                }
+            }
+            if self.is_primary(selection) {
+                primary_split = Some(splits.len());
+            }
+            remaining = remaining - 1 + pieces.len();
+            splits.push((idx, pieces));
+        }
+        if splits.is_empty() || remaining == 0 {
+            return SelectionDeltas::new();
+        }
+
+        let primary_from = self.primary().map(|s| s.from.clone());
+        let mut deltas = SelectionDeltas::with_capacity(splits.len() + remaining);
+        // Pieces stay within their selections, so the new list is built in one pass
+        // putting pieces in place of split selections:
+        let mut selections = Vec::with_capacity(remaining);
+        let mut created = Vec::with_capacity(splits.len());
+        let mut primary_idx = None;
+        let mut splits = splits.into_iter().enumerate().peekable();
+        for (old_idx, old) in mem::take(&mut self.selections).into_iter().enumerate() {
+            let Some((split_idx, (_, pieces))) = splits.next_if(|(_, (idx, _))| *idx == old_idx)
+            else {
+                selections.push(old);
+                continue;
+            };
+            let idx = selections.len();
+            if primary_split == Some(split_idx) && !pieces.is_empty() {
+                primary_idx = Some(idx);
+            }
+            selections.extend(pieces.into_iter().map(|s| self.identify(s)));
+            created.push(idx..selections.len());
+            self.forget_id(&old);
+            deltas.push_deleted(old, old_idx);
+        }
+        self.selections = selections;
+        for new_idx in created.iter().cloned().flatten() {
+            self.remember_id(new_idx);
+        }
+        if let Some(idx) = primary_idx {
+            self.set_primary_index(idx);
+        }
+        if let Some(from) = primary_from {
+            // The primary selection may be dropped completely, in this case the closest one
+            // becomes primary:
+            let idx = self.selections.partition_point(|s| s.from < from);
+            self.fix_primary(idx);
+        }
+        for new_idx in created.into_iter().flatten() {
+            deltas.push_created(&self.selections[new_idx], new_idx);
+        }
+
+        debug_assert!(self.is_state_correct());
+        deltas
+    }
+}
+
+/// Byte ranges of pieces of `haystack` between `separators` matches. Kept
+/// empty pieces are turned into ranges of a single separator character.
+fn piece_ranges(
+    haystack: &str,
+    separators: Vec<Range<usize>>,
+    keep_empty: bool,
+) -> Vec<Range<usize>> {
+    let char_at = |idx: usize| {
+        let len = haystack[idx..].chars().next().map_or(0, char::len_utf8);
+        idx..idx + len
+    };
+
+    let mut pieces: Vec<Range<usize>> = Vec::with_capacity(separators.len() + 1);
+    let mut push = |piece: Range<usize>| {
+        // Empty pieces around the last separator may point to the same character:
+        if pieces.last().is_none_or(|last| last.end <= piece.start) {
+            pieces.push(piece);
+        }
+    };
+
+    let mut start = 0;
+    for separator in separators.iter().filter(|s| !s.is_empty()) {
+        if start < separator.start {
+            push(start..separator.start);
+        } else if keep_empty {
+            push(char_at(separator.start));
+        }
+        start = separator.end;
+    }
+    if start < haystack.len() {
+        push(start..haystack.len());
+    } else if keep_empty {
+        if let Some((idx, _)) = haystack.char_indices().next_back() {
+            push(char_at(idx));
+        }
+    }
+
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use crate::{
+        test_utils::{selections_test, TestText},
+        Matcher,
+    };
+
+    /// Matches an empty range before every character.
+    struct EmptyMatches;
+
+    impl Matcher for EmptyMatches {
+        fn find_matches(&self, haystack: &str) -> Vec<Range<usize>> {
+            haystack.char_indices().map(|(idx, _)| idx..idx).collect()
+        }
+    }
+
+    #[test]
+    fn split() {
+        let text = TestText::new("a, bc, d\nef, g");
+        selections_test! {
+            [
+                (0, 0) - (0, 7),
+                (1, 0) - (1, 1),
+                (1, 4) - (1, 4),
+            ],
+            storage -> { storage.split_selections(&text, ", ", false) },
+            [
+                Deleted((0, 0) - (0, 7)),
+                Created((0, 0) - (0, 0)),
+                Created((0, 3) - (0, 4)),
+                Created((0, 7) - (0, 7)),
+            ],
+            [
+                (0, 0) - (0, 0),
+                (0, 3) - (0, 4),
+                (0, 7) - (0, 7),
+                (1, 0) - (1, 1),
+                (1, 4) - (1, 4),
+            ]
+        };
+    }
+
+    #[test]
+    fn split_lines() {
+        let text = TestText::new("ab\ncd\nef");
+        selections_test! {
+            [
+                (0, 1) - (2, 0),
+            ],
+            storage -> { storage.split_selections(&text, &'\n', false) },
+            [
+                Deleted((0, 1) - (2, 0)),
+                Created((0, 1) - (0, 1)),
+                Created((1, 0) - (1, 1)),
+                Created((2, 0) - (2, 0)),
+            ],
+            [
+                (0, 1) - (0, 1),
+                (1, 0) - (1, 1),
+                (2, 0) - (2, 0),
+            ]
+        };
+    }
+
+    #[test]
+    fn empty_pieces() {
+        let text = TestText::new(",a,,b,");
+        selections_test! {
+            [
+                (0, 0) - (0, 5),
+            ],
+            storage -> { storage.split_selections(&text, &',', false) },
+            [
+                Deleted((0, 0) - (0, 5)),
+                Created((0, 1) - (0, 1)),
+                Created((0, 4) - (0, 4)),
+            ],
+            [
+                (0, 1) - (0, 1),
+                (0, 4) - (0, 4),
+            ]
+        };
+        selections_test! {
+            [
+                (0, 0) - (0, 5),
+            ],
+            storage -> { storage.split_selections(&text, &',', true) },
+            [
+                Deleted((0, 0) - (0, 5)),
+                Created((0, 0) - (0, 0)),
+                Created((0, 1) - (0, 1)),
+                Created((0, 3) - (0, 3)),
+                Created((0, 4) - (0, 4)),
+                Created((0, 5) - (0, 5)),
+            ],
+            [
+                (0, 0) - (0, 0),
+                (0, 1) - (0, 1),
+                (0, 3) - (0, 3),
+                (0, 4) - (0, 4),
+                (0, 5) - (0, 5),
+            ]
+        };
+    }
+
+    #[test]
+    fn nothing_left() {
+        let text = TestText::new(",,");
+        selections_test! {
+            [
+                (0, 0) - (0, 1),
+            ],
+            storage -> { storage.split_selections(&text, &',', false) },
+            [],
+            [
+                (0, 0) - (0, 1),
+            ]
+        };
+    }
+
+    #[test]
+    fn unchanged() {
+        let text = TestText::new("a,b");
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+            ],
+            storage -> { storage.split_selections(&text, &EmptyMatches, false) },
+            [],
+            [
+                (0, 0) - (0, 2),
+            ]
+        };
+        selections_test! {
+            [
+                (0, 0) - (0, 0),
+                (0, 1) - (0, 1),
+            ],
+            storage -> { storage.split_selections(&text, &',', true) },
+            [],
+            [
+                (0, 0) - (0, 0),
+                (0, 1) - (0, 1),
+            ]
+        };
+    }
+}
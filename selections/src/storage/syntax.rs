@@ -0,0 +1,280 @@
+//! Selection of syntax tree nodes in [SelectionStorage].
+
+use super::SelectionStorage;
+use crate::{LineLength, Position, Selection, SelectionDeltas};
+
+/// Syntax tree of a buffer provided by a parser of the editor's choice.
+/// Node ranges are inclusive, just like selections.
+pub trait SyntaxTree {
+    /// Handle of a tree node.
+    type Node;
+
+    /// Returns the smallest node containing the range from `from` to `to`.
+    fn node_at(&self, from: &Position, to: &Position) -> Option<Self::Node>;
+
+    /// Returns first and last positions of the `node`.
+    fn node_range(&self, node: &Self::Node) -> (Position, Position);
+
+    /// Returns parent of the `node`, if it's not the root.
+    fn parent(&self, node: &Self::Node) -> Option<Self::Node>;
+
+    /// Returns the next sibling of the `node`.
+    fn next_sibling(&self, node: &Self::Node) -> Option<Self::Node>;
+
+    /// Returns the previous sibling of the `node`.
+    fn previous_sibling(&self, node: &Self::Node) -> Option<Self::Node>;
+
+    /// Returns children of the `node` in buffer order.
+    fn children(&self, node: &Self::Node) -> Vec<Self::Node>;
+}
+
+/// Node to select relatively to a selection, see
+/// [SelectionStorage::select_syntax_node].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyntaxNodeTarget {
+    /// The smallest node containing the selection.
+    Enclosing,
+    /// The smallest node containing the selection and larger than it.
+    Parent,
+    /// Next sibling of the node containing the selection.
+    NextSibling,
+    /// Previous sibling of the node containing the selection.
+    PreviousSibling,
+    /// The first child of the node containing the selection.
+    FirstChild,
+}
+
+impl<T: Clone> SelectionStorage<T> {
+    /// Replaces each selection with a node of the syntax `tree` chosen by
+    /// `target` relatively to the selection. Selections with no such node
+    /// are left as is, overlapping results are resolved according to the
+    /// storage's [CollisionConfig](crate::CollisionConfig).
+    pub fn select_syntax_node<S: SyntaxTree>(
+        &mut self,
+        line_lengths: &impl LineLength,
+        tree: &S,
+        target: SyntaxNodeTarget,
+    ) -> SelectionDeltas<'_, T> {
+        self.map_all(line_lengths, |s| {
+            match find_node(tree, &s.from, &s.to, target) {
+                Some((from, to)) => Selection {
+                    from,
+                    to,
+                    ..s.clone()
+                },
+                None => s.clone(),
+            }
+        })
+    }
+}
+
+/// Range of a node chosen by `target` relatively to the range from `from`
+/// to `to`.
+fn find_node<S: SyntaxTree>(
+    tree: &S,
+    from: &Position,
+    to: &Position,
+    target: SyntaxNodeTarget,
+) -> Option<(Position, Position)> {
+    let node = tree.node_at(from, to)?;
+    let node = match target {
+        SyntaxNodeTarget::Enclosing => node,
+        SyntaxNodeTarget::Parent => {
+            let mut node = node;
+            loop {
+                let (node_from, node_to) = tree.node_range(&node);
+                if &node_from < from || &node_to > to {
+                    break node;
+                }
+                node = tree.parent(&node)?;
+            }
+        }
+        SyntaxNodeTarget::NextSibling => tree.next_sibling(&node)?,
+        SyntaxNodeTarget::PreviousSibling => tree.previous_sibling(&node)?,
+        SyntaxNodeTarget::FirstChild => tree.children(&node).into_iter().next()?,
+    };
+    Some(tree.node_range(&node))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{selections_test, TestLineLengths};
+
+    /// Single line tree of nodes with their columns ranges and parents.
+    struct TestTree {
+        nodes: Vec<(usize, usize, Option<usize>)>,
+    }
+
+    impl TestTree {
+        /// Tree of `f(a, g(b))`.
+        fn new() -> Self {
+            TestTree {
+                nodes: vec![
+                    (0, 9, None),
+                    (0, 0, Some(0)),
+                    (1, 9, Some(0)),
+                    (2, 2, Some(2)),
+                    (5, 8, Some(2)),
+                    (5, 5, Some(4)),
+                    (6, 8, Some(4)),
+                    (7, 7, Some(6)),
+                ],
+            }
+        }
+
+        fn siblings(&self, node: usize) -> Vec<usize> {
+            (0..self.nodes.len())
+                .filter(|n| self.nodes[*n].2.is_some() && self.nodes[*n].2 == self.nodes[node].2)
+                .collect()
+        }
+    }
+
+    impl SyntaxTree for TestTree {
+        type Node = usize;
+
+        fn node_at(&self, from: &Position, to: &Position) -> Option<usize> {
+            (0..self.nodes.len())
+                .filter(|n| self.nodes[*n].0 <= from.column && self.nodes[*n].1 >= to.column)
+                .min_by_key(|n| self.nodes[*n].1 - self.nodes[*n].0)
+        }
+
+        fn node_range(&self, node: &usize) -> (Position, Position) {
+            let (from, to, _) = self.nodes[*node];
+            (Position::new(0, from), Position::new(0, to))
+        }
+
+        fn parent(&self, node: &usize) -> Option<usize> {
+            self.nodes[*node].2
+        }
+
+        fn next_sibling(&self, node: &usize) -> Option<usize> {
+            let siblings = self.siblings(*node);
+            let idx = siblings.iter().position(|n| n == node)?;
+            siblings.get(idx + 1).copied()
+        }
+
+        fn previous_sibling(&self, node: &usize) -> Option<usize> {
+            let siblings = self.siblings(*node);
+            let idx = siblings.iter().position(|n| n == node)?;
+            siblings.get(idx.checked_sub(1)?).copied()
+        }
+
+        fn children(&self, node: &usize) -> Vec<usize> {
+            (0..self.nodes.len())
+                .filter(|n| self.nodes[*n].2 == Some(*node))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn enclosing_and_parent() {
+        let tree = TestTree::new();
+        selections_test! {
+            [
+                (0, 2) - (0, 2),
+                (0, 5) - (0, 6),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 10);
+                storage.select_syntax_node(&line_lengths, &tree, SyntaxNodeTarget::Enclosing)
+            },
+            [
+                Updated {
+                    old: (0, 5) - (0, 6),
+                    new: (0, 5) - (0, 8),
+                },
+            ],
+            [
+                (0, 2) - (0, 2),
+                (0, 5) - (0, 8),
+            ]
+        };
+        selections_test! {
+            [
+                (0, 2) - (0, 2),
+                (0, 7) - (0, 7),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 10);
+                storage.select_syntax_node(&line_lengths, &tree, SyntaxNodeTarget::Parent)
+            },
+            [
+                Updated {
+                    old: (0, 2) - (0, 2),
+                    new: (0, 1) - (0, 9),
+                },
+                Deleted((0, 7) - (0, 7)),
+            ],
+            [
+                (0, 1) - (0, 9),
+            ]
+        };
+    }
+
+    #[test]
+    fn siblings_and_children() {
+        let tree = TestTree::new();
+        selections_test! {
+            [
+                (0, 2) - (0, 2),
+                (0, 5) - (0, 5),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 10);
+                storage.select_syntax_node(&line_lengths, &tree, SyntaxNodeTarget::NextSibling)
+            },
+            [
+                Updated {
+                    old: (0, 2) - (0, 2),
+                    new: (0, 5) - (0, 8),
+                },
+                Deleted((0, 5) - (0, 5)),
+            ],
+            [
+                (0, 5) - (0, 8),
+            ]
+        };
+        selections_test! {
+            [
+                (0, 5) - (0, 8),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 10);
+                storage.select_syntax_node(&line_lengths, &tree, SyntaxNodeTarget::PreviousSibling)
+            },
+            [
+                Updated {
+                    old: (0, 5) - (0, 8),
+                    new: (0, 2) - (0, 2),
+                },
+            ],
+            [
+                (0, 2) - (0, 2),
+            ]
+        };
+        selections_test! {
+            [
+                (0, 1) - (0, 9),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 10);
+                storage.select_syntax_node(&line_lengths, &tree, SyntaxNodeTarget::FirstChild)
+            },
+            [
+                Updated {
+                    old: (0, 1) - (0, 9),
+                    new: (0, 2) - (0, 2),
+                },
+            ],
+            [
+                (0, 2) - (0, 2),
+            ]
+        };
+    }
+}
@@ -6,7 +6,7 @@
 
 use crate::{
     test_utils::{selections_test, TestLineLengths},
-    Position,
+    CollisionConfig, CollisionPolicy, Position, Selection, SelectionStorage,
 };
 
 mod left_single {
@@ -697,6 +697,35 @@ mod up_single {
             ]
         }
     }
+
+    #[test]
+    fn overlap_over_selection_in_between() {
+        selections_test! {
+            [
+                (0, 0) - (0, 5),
+                (1, 0) - (1, 1),
+                (2, 3) - (2, 3),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 20);
+                line_lengths.set(1, 20);
+                line_lengths.set(2, 20);
+                storage.move_up_single(&line_lengths, &Position::new(2, 3), 2, false)
+            },
+            [
+                Deleted((0, 0) - (0, 5)),
+                Updated {
+                    old: (2, 3) - (2, 3),
+                    new: (0, 3) - (0, 3),
+                },
+            ],
+            [
+                (0, 3) - (0, 3),
+                (1, 0) - (1, 1),
+            ]
+        }
+    }
 }
 
 mod down_single {
@@ -950,3 +979,407 @@ mod down_single {
         }
     }
 }
+
+mod subword {
+    use super::*;
+    use crate::test_utils::TestText;
+
+    #[test]
+    fn single() {
+        selections_test! {
+            [
+                (0, 0) - (0, 0),
+                (0, 9) - (0, 9),
+            ],
+            storage -> {
+                let text = TestText::new("parseHttpRequest");
+                storage.move_subword_forward_single(&text, &Position::new(0, 0), 2, true)
+            },
+            [
+                Updated {
+                    old: (0, 0) - (0, 0),
+                    new: (0, 0) - (0, 9),
+                },
+                Deleted((0, 9) - (0, 9)),
+            ],
+            [
+                (0, 0) - (0, 9),
+            ]
+        };
+    }
+
+    #[test]
+    fn all() {
+        selections_test! {
+            [
+                (0, 1) - (0, 1),
+                (0, 7) - (0, 7),
+                (0, 14) - (0, 14),
+            ],
+            storage -> {
+                let text = TestText::new("snake_case_name");
+                storage.move_subword_backward_all(&text, 1, false)
+            },
+            [
+                Updated {
+                    old: (0, 1) - (0, 1),
+                    new: (0, 0) - (0, 0),
+                },
+                Updated {
+                    old: (0, 7) - (0, 7),
+                    new: (0, 6) - (0, 6),
+                },
+                Updated {
+                    old: (0, 14) - (0, 14),
+                    new: (0, 11) - (0, 11),
+                },
+            ],
+            [
+                (0, 0) - (0, 0),
+                (0, 6) - (0, 6),
+                (0, 11) - (0, 11),
+            ]
+        };
+    }
+
+    #[test]
+    fn all_reject_new() {
+        selections_test! {
+            [
+                (0, 1) - (0, 1),
+                (0, 3) - (0, 3),
+            ],
+            storage -> {
+                let text = TestText::new("parseHttpRequest");
+                storage.set_collision_config(CollisionConfig {
+                    policy: CollisionPolicy::RejectNew,
+                    ..Default::default()
+                });
+                storage.move_subword_forward_all(&text, 1, false)
+            },
+            [],
+            [
+                (0, 1) - (0, 1),
+                (0, 3) - (0, 3),
+            ]
+        };
+    }
+}
+
+mod sentence {
+    use super::*;
+    use crate::test_utils::TestText;
+
+    #[test]
+    fn single() {
+        selections_test! {
+            [
+                (0, 2) - (0, 2),
+                (1, 0) - (1, 2),
+            ],
+            storage -> {
+                let text = TestText::new("One two. Three\nfour!  Five");
+                storage.move_sentence_forward_single(&text, &Position::new(0, 2), 2, true)
+            },
+            [
+                Updated {
+                    old: (0, 2) - (0, 2),
+                    new: (0, 2) - (1, 7),
+                },
+                Deleted((1, 0) - (1, 2)),
+            ],
+            [
+                (0, 2) - (1, 7),
+            ]
+        };
+    }
+
+    #[test]
+    fn all() {
+        selections_test! {
+            [
+                (0, 3) - (0, 3),
+                (1, 9) - (1, 9),
+            ],
+            storage -> {
+                let text = TestText::new("One two. Three\nfour!  Five");
+                storage.move_sentence_backward_all(&text, 1, false)
+            },
+            [
+                Updated {
+                    old: (0, 3) - (0, 3),
+                    new: (0, 0) - (0, 0),
+                },
+                Updated {
+                    old: (1, 9) - (1, 9),
+                    new: (1, 7) - (1, 7),
+                },
+            ],
+            [
+                (0, 0) - (0, 0),
+                (1, 7) - (1, 7),
+            ]
+        };
+    }
+}
+
+mod collision_config {
+    use super::*;
+
+    #[test]
+    fn reject_new() {
+        selections_test! {
+            [
+                (0, 0) - (0, 0),
+                (0, 5) - (0, 5),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 20);
+                storage.set_collision_config(CollisionConfig {
+                    policy: CollisionPolicy::RejectNew,
+                    ..Default::default()
+                });
+                storage.move_right_single(&line_lengths, &Position::new(0, 0), 5, false)
+            },
+            [],
+            [
+                (0, 0) - (0, 0),
+                (0, 5) - (0, 5),
+            ]
+        };
+    }
+
+    #[test]
+    fn keep_existing() {
+        selections_test! {
+            [
+                (0, 0) - (0, 0),
+                (0, 5) - (0, 5),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 20);
+                storage.set_collision_config(CollisionConfig {
+                    policy: CollisionPolicy::KeepExisting,
+                    ..Default::default()
+                });
+                storage.move_right_single(&line_lengths, &Position::new(0, 0), 5, false)
+            },
+            [
+                Deleted((0, 0) - (0, 0)),
+            ],
+            [
+                (0, 5) - (0, 5),
+            ]
+        };
+    }
+
+    #[test]
+    fn merge_extend() {
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+                (0, 5) - (0, 8),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 20);
+                storage.move_right_single(&line_lengths, &Position::new(0, 0), 4, true)
+            },
+            [
+                Updated {
+                    old: (0, 0) - (0, 2),
+                    new: (0, 0) - (0, 8),
+                },
+                Deleted((0, 5) - (0, 8)),
+            ],
+            [
+                (0, 0) - (0, 8),
+            ]
+        };
+    }
+
+    #[test]
+    fn replace_extend() {
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+                (0, 5) - (0, 8),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 20);
+                storage.set_collision_config(CollisionConfig {
+                    policy: CollisionPolicy::Replace,
+                    ..Default::default()
+                });
+                storage.move_right_single(&line_lengths, &Position::new(0, 0), 4, true)
+            },
+            [
+                Updated {
+                    old: (0, 0) - (0, 2),
+                    new: (0, 0) - (0, 6),
+                },
+                Deleted((0, 5) - (0, 8)),
+            ],
+            [
+                (0, 0) - (0, 6),
+            ]
+        };
+    }
+
+    #[test]
+    fn trim_to_fit() {
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+                (0, 5) - (0, 6),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 20);
+                storage.set_collision_config(CollisionConfig {
+                    policy: CollisionPolicy::TrimToFit,
+                    ..Default::default()
+                });
+                storage.move_right_single(&line_lengths, &Position::new(0, 0), 6, true)
+            },
+            [
+                Updated {
+                    old: (0, 0) - (0, 2),
+                    new: (0, 7) - (0, 8),
+                },
+            ],
+            [
+                (0, 5) - (0, 6),
+                (0, 7) - (0, 8),
+            ]
+        };
+    }
+
+    #[test]
+    fn trim_to_fit_cursor_inside() {
+        selections_test! {
+            [
+                (0, 0) - (0, 2),
+                (0, 5) - (0, 8),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 20);
+                storage.set_collision_config(CollisionConfig {
+                    policy: CollisionPolicy::TrimToFit,
+                    ..Default::default()
+                });
+                storage.move_right_single(&line_lengths, &Position::new(0, 0), 4, true)
+            },
+            [],
+            [
+                (0, 0) - (0, 2),
+                (0, 5) - (0, 8),
+            ]
+        };
+    }
+
+    #[test]
+    fn merge_touching() {
+        selections_test! {
+            [
+                (0, 0) - (0, 0),
+                (0, 3) - (0, 5),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 20);
+                storage.set_collision_config(CollisionConfig {
+                    merge_touching: true,
+                    ..Default::default()
+                });
+                storage.move_right_single(&line_lengths, &Position::new(0, 0), 2, false)
+            },
+            [
+                Updated {
+                    old: (0, 0) - (0, 0),
+                    new: (0, 2) - (0, 2),
+                },
+                Deleted((0, 3) - (0, 5)),
+            ],
+            [
+                (0, 2) - (0, 2),
+            ]
+        };
+    }
+
+    #[test]
+    fn merge_touching_no_extend_overlap() {
+        selections_test! {
+            [
+                (0, 0) - (0, 0),
+                (0, 3) - (0, 9),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 20);
+                storage.set_collision_config(CollisionConfig {
+                    merge_touching: true,
+                    ..Default::default()
+                });
+                storage.move_right_single(&line_lengths, &Position::new(0, 0), 4, false)
+            },
+            [
+                Updated {
+                    old: (0, 0) - (0, 0),
+                    new: (0, 4) - (0, 4),
+                },
+                Deleted((0, 3) - (0, 9)),
+            ],
+            [
+                (0, 4) - (0, 4),
+            ]
+        };
+    }
+
+    #[test]
+    fn per_call_config() {
+        selections_test! {
+            [
+                (0, 0) - (0, 0),
+                (0, 3) - (0, 5),
+            ],
+            storage -> {
+                let mut line_lengths = TestLineLengths::new();
+                line_lengths.set(0, 20);
+                let config = CollisionConfig {
+                    policy: CollisionPolicy::KeepExisting,
+                    ..Default::default()
+                };
+                storage.move_with_config(&line_lengths, &Position::new(0, 0), false, config, |_| {
+                    Selection::new(Position::new(0, 4), Position::new(0, 4))
+                })
+            },
+            [
+                Deleted((0, 0) - (0, 0)),
+            ],
+            [
+                (0, 3) - (0, 5),
+            ]
+        };
+    }
+
+    #[test]
+    fn custom_motion_keeps_id() {
+        let mut line_lengths = TestLineLengths::new();
+        line_lengths.set(0, 20);
+        let mut storage = SelectionStorage::new();
+        let id = storage.primary().and_then(Selection::selection_id).unwrap();
+
+        storage.move_with_config(&line_lengths, id, false, Default::default(), |_| {
+            Selection::new(Position::new(0, 10), Position::new(0, 10))
+        });
+        assert_eq!(
+            storage.get(id),
+            Some(&Selection::new(Position::new(0, 10), Position::new(0, 10)))
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! Tracking of [SelectionStorage] changes made by its operations.
+
+use super::SelectionStorage;
+use crate::{
+    deltas::CloneSelection, DeltaIndices, OwnedSelectionDelta, OwnedSelectionDeltas,
+    SelectionDeltas,
+};
+
+impl<T> SelectionStorage<T> {
+    /// Enables or disables annotation of deltas with storage indices of
+    /// changed selections (see [SelectionDeltas::indices]), returns previous
+    /// setting. Indices are recorded by operations as they go, observers
+    /// receive them too. Disabled by default.
+    pub fn set_delta_indices(&mut self, enabled: bool) -> bool {
+        std::mem::replace(&mut self.delta_indices, enabled)
+    }
+
+    /// Runs a mutating operation and passes its deltas to those who track
+    /// storage changes, if any.
+    pub(crate) fn tracked<F>(&mut self, op: F) -> SelectionDeltas<'_, T>
+    where
+        F: for<'b> FnOnce(&'b mut SelectionStorage<T>) -> SelectionDeltas<'b, T>,
+    {
+        let delta_indices = self.delta_indices;
+        let clone = match self.clone_selection {
+            Some(clone) if self.is_tracked() => clone,
+            _ => {
+                let mut deltas = op(self);
+                if !delta_indices {
+                    deltas.strip_indices();
+                }
+                return deltas;
+            }
+        };
+
+        // Borrowed deltas would keep the storage borrowed, so they're detached and then
+        // borrowed again.
+        let mut deltas = op(self).into_owned_with(clone);
+        let indices = deltas.indices().map(<[_]>::to_vec).unwrap_or_default();
+        if !delta_indices {
+            deltas.strip_indices();
+        }
+        self.track(&deltas, clone);
+        let mut deltas = self.borrow_deltas(deltas, indices);
+        if !delta_indices {
+            deltas.strip_indices();
+        }
+        deltas
+    }
+
+    /// Checks if anyone tracks storage changes, otherwise deltas are returned
+    /// as is.
+    fn is_tracked(&self) -> bool {
+        !self.observers.is_empty() || !self.transactions.is_empty()
+    }
+
+    /// Passes deltas to those who track storage changes.
+    fn track(&mut self, deltas: &OwnedSelectionDeltas<T>, clone: CloneSelection<T>) {
+        if deltas.is_empty() {
+            return;
+        }
+        // Observers are notified with deltas of the whole transaction on commit:
+        if let Some(transaction) = self.transactions.last_mut() {
+            transaction.deltas.push(deltas.clone_with(clone));
+        } else {
+            self.observers.notify(deltas);
+        }
+    }
+
+    /// Turns owned deltas of the last operation back into deltas borrowing new
+    /// states from the storage, `indices` are ones recorded by the operation.
+    fn borrow_deltas(
+        &self,
+        deltas: OwnedSelectionDeltas<T>,
+        indices: Vec<DeltaIndices>,
+    ) -> SelectionDeltas<'_, T> {
+        let mut result = SelectionDeltas::with_capacity(deltas.len());
+        result.set_merges(deltas.merges().to_vec());
+        for (
+            delta,
+            DeltaIndices {
+                old: old_idx,
+                new: new_idx,
+            },
+        ) in deltas.into_iter().zip(indices)
+        {
+            match (delta, old_idx, new_idx) {
+                (OwnedSelectionDelta::Created(_), _, Some(new_idx)) => {
+                    result.push_created(&self.selections[new_idx], new_idx)
+                }
+                (OwnedSelectionDelta::Deleted(old), Some(old_idx), _) => {
+                    result.push_deleted(old, old_idx)
+                }
+                (OwnedSelectionDelta::Updated { old, .. }, Some(old_idx), Some(new_idx)) => {
+                    result.push_updated(old, old_idx, &self.selections[new_idx], new_idx)
+                }
+                _ => debug_assert!(false, "indices must match the delta"),
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{test_utils::TestLineLengths, Position, Selection, SelectionDelta};
+
+    /// Applies deltas to `list` as splice operations using their indices.
+    fn splice(list: &mut Vec<Selection>, deltas: &SelectionDeltas) {
+        let indices = deltas.indices().expect("indices are enabled");
+        let mut removed: Vec<usize> = indices.iter().filter_map(|i| i.old).collect();
+        removed.sort_unstable();
+        for idx in removed.into_iter().rev() {
+            list.remove(idx);
+        }
+        let mut inserted: Vec<(usize, &Selection)> = deltas
+            .iter()
+            .zip(indices)
+            .filter_map(|(delta, i)| match delta {
+                SelectionDelta::Created(new) | SelectionDelta::Updated { new, .. } => {
+                    Some((i.new?, *new))
+                }
+                SelectionDelta::Deleted(_) => None,
+            })
+            .collect();
+        inserted.sort_unstable_by_key(|(idx, _)| *idx);
+        for (idx, s) in inserted {
+            list.insert(idx, s.clone());
+        }
+    }
+
+    #[test]
+    fn no_indices_by_default() {
+        let mut storage = SelectionStorage::new();
+        let deltas = storage.insert(Selection::new(Position::new(1, 0), Position::new(1, 1)));
+        assert_eq!(deltas.indices(), None);
+    }
+
+    #[test]
+    fn insert_indices() {
+        let mut storage = SelectionStorage::new();
+        storage.insert(Selection::new(Position::new(1, 3), Position::new(3, 7)));
+        storage.insert(Selection::new(Position::new(4, 3), Position::new(5, 7)));
+        storage.insert(Selection::new(Position::new(6, 3), Position::new(6, 7)));
+        assert!(!storage.set_delta_indices(true));
+
+        let deltas = storage.insert(Selection::new(Position::new(3, 5), Position::new(4, 7)));
+        assert_eq!(
+            deltas.indices(),
+            Some(
+                [
+                    DeltaIndices {
+                        old: Some(1),
+                        new: None,
+                    },
+                    DeltaIndices {
+                        old: None,
+                        new: Some(1),
+                    },
+                    DeltaIndices {
+                        old: Some(2),
+                        new: None,
+                    },
+                ]
+                .as_slice()
+            )
+        );
+    }
+
+    #[test]
+    fn splice_with_indices() {
+        let mut line_lengths = TestLineLengths::new();
+        for line in 0..10 {
+            line_lengths.set(line, 10);
+        }
+        let mut storage = SelectionStorage::new();
+        storage.set_delta_indices(true);
+        let mut list: Vec<Selection> = storage.iter_all().cloned().collect();
+
+        let deltas = storage.insert(Selection::new(Position::new(2, 0), Position::new(2, 3)));
+        splice(&mut list, &deltas);
+        let deltas = storage.insert(Selection::new(Position::new(4, 0), Position::new(4, 3)));
+        splice(&mut list, &deltas);
+        let deltas = storage.insert(Selection::new(Position::new(5, 5), Position::new(5, 8)));
+        splice(&mut list, &deltas);
+        assert_eq!(list, storage.iter_all().cloned().collect::<Vec<_>>());
+
+        let deltas = storage.move_down_single(&line_lengths, &Position::new(0, 0), 3, false);
+        splice(&mut list, &deltas);
+        assert_eq!(list, storage.iter_all().cloned().collect::<Vec<_>>());
+
+        let deltas = storage.map_all(&line_lengths, |s| {
+            let mut s = s.clone();
+            s.from.line = s.from.line.saturating_sub(1);
+            s.from.column = 0;
+            s
+        });
+        splice(&mut list, &deltas);
+        assert_eq!(list, storage.iter_all().cloned().collect::<Vec<_>>());
+
+        let deltas = storage.extend_from([
+            Selection::new(Position::new(0, 2), Position::new(0, 4)),
+            Selection::new(Position::new(8, 0), Position::new(8, 1)),
+            Selection::new(Position::new(6, 0), Position::new(6, 1)),
+        ]);
+        splice(&mut list, &deltas);
+        assert_eq!(list, storage.iter_all().cloned().collect::<Vec<_>>());
+
+        let deltas = storage.insert(Selection::new(Position::new(5, 0), Position::new(6, 5)));
+        splice(&mut list, &deltas);
+        let deltas = deltas.into_owned();
+        let deltas = storage.apply_deltas(&deltas.invert());
+        splice(&mut list, &deltas);
+        assert_eq!(list, storage.iter_all().cloned().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn observed_indices() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        let mut storage = SelectionStorage::new();
+        storage.subscribe(move |deltas| {
+            received_clone
+                .lock()
+                .unwrap()
+                .push(deltas.indices().map(<[_]>::to_vec))
+        });
+
+        storage.insert(Selection::new(Position::new(1, 0), Position::new(1, 1)));
+        storage.set_delta_indices(true);
+        storage.insert(Selection::new(Position::new(0, 3), Position::new(0, 5)));
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            [
+                None,
+                Some(vec![DeltaIndices {
+                    old: None,
+                    new: Some(1),
+                }]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! Transactions on [SelectionStorage].
+
+use super::SelectionStorage;
+use crate::{DeltasAccumulator, OwnedSelectionDeltas, Selection, SelectionId};
+
+/// State of a transaction in progress.
+#[derive(Debug)]
+pub(crate) struct Transaction<T> {
+    /// Selections before the transaction.
+    selections: Vec<Selection<T>>,
+    /// Primary selection before the transaction.
+    primary: Option<SelectionId>,
+    /// Deltas of operations made during the transaction.
+    pub(crate) deltas: DeltasAccumulator<T>,
+}
+
+impl<T: Clone> SelectionStorage<T> {
+    /// Starts a transaction: changes made until [Self::commit] could be
+    /// reverted with [Self::rollback]. Transactions could be nested.
+    pub fn begin_transaction(&mut self) {
+        self.clone_selection = Some(Selection::clone);
+        self.transactions.push(Transaction {
+            selections: self.selections.clone(),
+            primary: self.primary,
+            deltas: DeltasAccumulator::new(),
+        });
+    }
+
+    /// Checks if there is a transaction in progress.
+    pub fn in_transaction(&self) -> bool {
+        !self.transactions.is_empty()
+    }
+
+    /// Finishes the latest transaction returning net deltas of all its
+    /// operations. In case of a nested transaction its deltas become a part
+    /// of the outer one, otherwise observers are notified. Returns `None` if
+    /// there is no transaction.
+    pub fn commit(&mut self) -> Option<OwnedSelectionDeltas<T>> {
+        let mut transaction = self.transactions.pop()?;
+        let deltas = transaction.deltas.take();
+        if let Some(outer) = self.transactions.last_mut() {
+            outer.deltas.push(deltas.clone());
+        } else if !deltas.is_empty() {
+            self.observers.notify(&deltas);
+        }
+        Some(deltas)
+    }
+
+    /// Cancels the latest transaction restoring the state before it with no
+    /// deltas. Returns `false` if there is no transaction.
+    pub fn rollback(&mut self) -> bool {
+        match self.transactions.pop() {
+            Some(transaction) => {
+                self.selections = transaction.selections;
+                self.primary = transaction.primary;
+                self.rebuild_ids();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{test_utils::TestLineLengths, OwnedSelectionDelta, Position};
+
+    fn state(storage: &SelectionStorage) -> Vec<Selection> {
+        storage.iter_all().cloned().collect()
+    }
+
+    #[test]
+    fn commit() {
+        let line_lengths = TestLineLengths::from_lengths([20, 5, 20]);
+        let mut storage = SelectionStorage::new();
+        storage.begin_transaction();
+        assert!(storage.in_transaction());
+
+        storage.insert(Selection::new(Position::new(1, 0), Position::new(1, 3)));
+        storage.move_right_single(&line_lengths, &Position::new(1, 0), 1, false);
+        storage.move_right_single(&line_lengths, &Position::new(0, 0), 2, true);
+
+        assert_eq!(
+            storage.commit().map(|d| d.into_iter().collect::<Vec<_>>()),
+            Some(vec![
+                OwnedSelectionDelta::Updated {
+                    old: Selection::new(Position::new(0, 0), Position::new(0, 0)),
+                    new: Selection::new(Position::new(0, 0), Position::new(0, 2)),
+                },
+                OwnedSelectionDelta::Created(Selection::new(
+                    Position::new(1, 4),
+                    Position::new(1, 4)
+                )),
+            ])
+        );
+        assert!(!storage.in_transaction());
+        assert!(storage.commit().is_none());
+    }
+
+    #[test]
+    fn rollback() {
+        let line_lengths = TestLineLengths::from_lengths([20, 5, 20]);
+        let mut storage = SelectionStorage::new();
+        storage.insert(Selection::new(Position::new(0, 12), Position::new(2, 12)));
+        storage.move_up_single(&line_lengths, &Position::new(0, 12), 1, true);
+        storage.set_primary(&Position::new(0, 0));
+        let before = state(&storage);
+
+        storage.begin_transaction();
+        storage.insert(Selection::new(Position::new(1, 0), Position::new(1, 3)));
+        storage.move_down_single(&line_lengths, &Position::new(0, 12), 1, false);
+        assert!(storage.rollback());
+
+        assert_eq!(state(&storage), before);
+        assert_eq!(
+            storage.primary(),
+            Some(&Selection::new(Position::new(0, 0), Position::new(0, 0)))
+        );
+        assert!(!storage.rollback());
+    }
+
+    #[test]
+    fn nested() {
+        let line_lengths = TestLineLengths::from_lengths([20, 5, 20]);
+        let mut storage = SelectionStorage::new();
+        storage.begin_transaction();
+        storage.move_right_single(&line_lengths, &Position::new(0, 0), 2, false);
+
+        storage.begin_transaction();
+        storage.insert(Selection::new(Position::new(1, 0), Position::new(1, 3)));
+        assert!(storage.rollback());
+
+        storage.begin_transaction();
+        storage.move_right_single(&line_lengths, &Position::new(0, 2), 2, false);
+        assert_eq!(storage.commit().map(|d| d.len()), Some(1));
+
+        assert_eq!(
+            storage.commit().map(|d| d.into_iter().collect::<Vec<_>>()),
+            Some(vec![OwnedSelectionDelta::Updated {
+                old: Selection::new(Position::new(0, 0), Position::new(0, 0)),
+                new: Selection::new(Position::new(0, 4), Position::new(0, 4)),
+            }])
+        );
+    }
+}
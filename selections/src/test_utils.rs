@@ -164,15 +164,21 @@ macro_rules! selections_test {
         ] $($rest)* }
     };
 
-    // Finalize selections helper array builder
+    // Finalize selections helper array builder (typed explicitly as it might be empty if no
+    // deltas are expected or all of them are `Deleted`)
     (@deltas_selections [$($acc:tt)*] $(,)?) => {
-        [$($acc)*]
+        {
+            let selections: ::std::vec::Vec<$crate::Selection> = vec![$($acc)*];
+            selections
+        }
     };
 
     // Section of deltas assertions
     (@deltas_start $deltas_ident:ident $deltas_pos:ident $($rest:tt)*) => {
         {
             let mut deltas_iter = $deltas_ident.into_iter();
+            // Helper array is not referenced if there are no `Created` or `Updated` deltas
+            let _ = &$deltas_pos;
             let expected_deltas = selections_test! { @deltas_exp $deltas_pos (0) [] $($rest)* };
             for right in expected_deltas.into_iter() {
                 ::pretty_assertions::assert_eq!(
@@ -245,11 +251,48 @@ macro_rules! selections_test {
     };
 }
 
-use std::collections::BTreeMap;
+use std::{borrow::Cow, collections::BTreeMap};
 
 pub(crate) use selections_test;
 
-use crate::LineLength;
+use crate::{LineLength, LineText, Position, Selection, SelectionStorage};
+
+/// Selection from `(line, column)` pairs of its anchor and cursor.
+pub(crate) fn selection(anchor: (usize, usize), cursor: (usize, usize)) -> Selection {
+    selection_with_payload(anchor, cursor, ())
+}
+
+/// Selection with a payload from `(line, column)` pairs of its anchor and
+/// cursor.
+pub(crate) fn selection_with_payload<T>(
+    anchor: (usize, usize),
+    cursor: (usize, usize),
+    payload: T,
+) -> Selection<T> {
+    Selection::with_payload(
+        Position::new(anchor.0, anchor.1),
+        Position::new(cursor.0, cursor.1),
+        payload,
+    )
+}
+
+/// Storage with the default selection and `selections` inserted one by one.
+pub(crate) fn storage(selections: impl IntoIterator<Item = Selection>) -> SelectionStorage {
+    storage_with_payload((), selections)
+}
+
+/// Storage with the default selection having `payload` and `selections`
+/// inserted one by one.
+pub(crate) fn storage_with_payload<T>(
+    payload: T,
+    selections: impl IntoIterator<Item = Selection<T>>,
+) -> SelectionStorage<T> {
+    let mut storage = SelectionStorage::with_payload(payload);
+    for selection in selections {
+        storage.insert(selection);
+    }
+    storage
+}
 
 #[derive(Debug)]
 pub(crate) struct TestLineLengths {
@@ -265,6 +308,13 @@ impl TestLineLengths {
         line_lengths
     }
 
+    /// Lengths of lines in order, starting from the first one.
+    pub(crate) fn from_lengths(lengths: impl IntoIterator<Item = usize>) -> Self {
+        TestLineLengths {
+            line_length: lengths.into_iter().enumerate().collect(),
+        }
+    }
+
     pub(crate) fn set(&mut self, line: usize, length: usize) {
         self.line_length.insert(line, length);
     }
@@ -279,3 +329,32 @@ impl LineLength for TestLineLengths {
         self.line_length.len()
     }
 }
+
+/// Buffer text for tests, lines are separated with `\n`.
+pub(crate) struct TestText {
+    lines: Vec<String>,
+}
+
+impl TestText {
+    pub(crate) fn new(text: &str) -> Self {
+        TestText {
+            lines: text.split('\n').map(ToOwned::to_owned).collect(),
+        }
+    }
+}
+
+impl LineLength for TestText {
+    fn get_len(&self, line: usize) -> Option<usize> {
+        self.lines.get(line).map(|l| l.chars().count())
+    }
+
+    fn lines_count(&self) -> usize {
+        self.lines.len()
+    }
+}
+
+impl LineText for TestText {
+    fn get_line(&self, line: usize) -> Option<Cow<'_, str>> {
+        self.lines.get(line).map(|l| Cow::Borrowed(l.as_str()))
+    }
+}
@@ -0,0 +1,419 @@
+//! Helpers to work with buffer text provided by [LineText].
+
+mod objects;
+
+use std::ops::Range;
+
+pub use objects::TextObject;
+
+use crate::{LineText, Position};
+
+/// Buffer text (or a range of its lines) gathered from [LineText] with lines
+/// joined by newlines, so text could be searched across lines.
+pub(crate) struct BufferText {
+    text: String,
+    /// Byte offsets of lines beginnings.
+    line_starts: Vec<usize>,
+    /// Buffer line the text starts with.
+    first_line: usize,
+    /// Whether the text ends with the last buffer line.
+    ends_buffer: bool,
+}
+
+impl BufferText {
+    /// Gathers text of all lines of the buffer.
+    pub(crate) fn new(text: &impl LineText) -> Self {
+        Self::lines(text, 0..text.lines_count())
+    }
+
+    /// Gathers text of `lines` only, lines out of the buffer are skipped.
+    /// Positions are still the buffer ones.
+    pub(crate) fn lines(text: &impl LineText, lines: Range<usize>) -> Self {
+        let lines = lines.start..lines.end.min(text.lines_count());
+        let mut result = String::new();
+        let mut line_starts = Vec::with_capacity(lines.len());
+        for line in lines.clone() {
+            if line > lines.start {
+                result.push('\n');
+            }
+            line_starts.push(result.len());
+            if let Some(content) = text.get_line(line) {
+                result.push_str(&content);
+            }
+        }
+
+        BufferText {
+            text: result,
+            line_starts,
+            first_line: lines.start,
+            ends_buffer: lines.end == text.lines_count(),
+        }
+    }
+
+    /// Returns the whole text.
+    pub(crate) fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Byte offset of `position`, positions out of the text are clamped to
+    /// its beginning or end.
+    pub(crate) fn offset(&self, position: &Position) -> usize {
+        let Some(line) = position.line.checked_sub(self.first_line) else {
+            return 0;
+        };
+        let Some(&line_start) = self.line_starts.get(line) else {
+            return self.text.len();
+        };
+        self.text[line_start..]
+            .char_indices()
+            .nth(position.column)
+            .map_or(self.text.len(), |(idx, _)| line_start + idx)
+    }
+
+    /// Position of a character on byte `offset`.
+    pub(crate) fn position(&self, offset: usize) -> Position {
+        let line = self.line_starts.partition_point(|start| *start <= offset) - 1;
+        let column = self.text[self.line_starts[line]..offset].chars().count();
+        Position::new(self.first_line + line, column)
+    }
+
+    /// First and last (inclusive) positions of a non-empty byte `range`.
+    pub(crate) fn range_positions(&self, range: &Range<usize>) -> (Position, Position) {
+        (
+            self.position(range.start),
+            self.position(self.last_char_offset(range.end)),
+        )
+    }
+
+    /// Byte offset right after a character on `offset`.
+    pub(crate) fn next_offset(&self, offset: usize) -> usize {
+        self.text[offset..]
+            .chars()
+            .next()
+            .map_or(self.text.len(), |ch| offset + ch.len_utf8())
+    }
+
+    /// Byte offset of the last character of a non-empty byte range ending on
+    /// `end`.
+    pub(crate) fn last_char_offset(&self, end: usize) -> usize {
+        self.text[..end]
+            .char_indices()
+            .next_back()
+            .map_or(0, |(idx, _)| idx)
+    }
+
+    /// Byte ranges of sentences in order. A sentence begins with a
+    /// non-whitespace character and ends with `.`, `!` or `?` (with closing
+    /// quotes and brackets right after it) followed by whitespace or the text
+    /// end. A blank line ends a sentence too.
+    pub(crate) fn sentences(&self) -> Vec<Range<usize>> {
+        let mut sentences = Vec::new();
+        let mut start = None;
+        let mut end = 0;
+        let mut chars = self.text.char_indices().peekable();
+        while let Some((idx, ch)) = chars.next() {
+            if ch.is_whitespace() {
+                if ch == '\n' && self.is_blank_line(idx + 1) {
+                    if let Some(start) = start.take() {
+                        sentences.push(start..end);
+                    }
+                }
+                continue;
+            }
+
+            let sentence_start = *start.get_or_insert(idx);
+            end = idx + ch.len_utf8();
+            if matches!(ch, '.' | '!' | '?') {
+                while let Some((idx, ch)) = chars.next_if(|(_, ch)| "\"')]".contains(*ch)) {
+                    end = idx + ch.len_utf8();
+                }
+                if chars.peek().is_none_or(|(_, ch)| ch.is_whitespace()) {
+                    sentences.push(sentence_start..end);
+                    start = None;
+                }
+            }
+        }
+        if let Some(start) = start {
+            sentences.push(start..end);
+        }
+        sentences
+    }
+
+    /// Positions sentence motions stop on: beginnings of sentences, first
+    /// lines of blank line runs, and the buffer beginning and end if the
+    /// text has them.
+    pub(crate) fn sentence_stops(&self) -> Vec<Position> {
+        let mut stops: Vec<usize> = self.sentences().iter().map(|s| s.start).collect();
+        stops.extend(
+            self.line_starts
+                .iter()
+                .enumerate()
+                .filter(|(line, start)| {
+                    self.is_blank_line(**start)
+                        && (*line == 0 || !self.is_blank_line(self.line_starts[line - 1]))
+                })
+                .map(|(_, start)| *start),
+        );
+        if self.first_line == 0 {
+            stops.push(0);
+        }
+        if self.ends_buffer {
+            stops.push(self.text.len());
+        }
+        stops.sort_unstable();
+        stops.dedup();
+
+        if self.line_starts.is_empty() {
+            return vec![Position::new(self.first_line, 0)];
+        }
+        stops
+            .into_iter()
+            .map(|offset| self.position(offset))
+            .collect()
+    }
+
+    /// Checks if a line beginning on byte offset `start` has only whitespace.
+    fn is_blank_line(&self, start: usize) -> bool {
+        self.text[start..]
+            .split('\n')
+            .next()
+            .is_some_and(|line| line.trim().is_empty())
+    }
+}
+
+/// Sentence stops (see [BufferText::sentence_stops]) around `position`, at
+/// least `n` of them after it (or before it if not `forward`) unless the
+/// buffer ends first. Sentences don't cross blank lines, so lines are gathered
+/// by blocks of non-blank (or blank) lines instead of the whole buffer.
+pub(crate) fn sentence_stops_around(
+    text: &impl LineText,
+    position: &Position,
+    n: usize,
+    forward: bool,
+) -> Vec<Position> {
+    let Some(last_line) = text.lines_count().checked_sub(1) else {
+        return vec![Position::new(0, 0)];
+    };
+    let line = position.line.min(last_line);
+    let blank = is_blank(text, line).unwrap_or_default();
+    let (mut start, mut end) = (block_start(text, line, blank), block_end(text, line, blank));
+    loop {
+        let stops = BufferText::lines(text, start..end + 1).sentence_stops();
+        if forward {
+            let ahead = stops.len() - stops.partition_point(|p| p <= position);
+            if ahead >= n || end == last_line {
+                return stops;
+            }
+            end = block_end(text, end + 1, is_blank(text, end + 1).unwrap_or_default());
+        } else {
+            let behind = stops.partition_point(|p| p < position);
+            if behind >= n || start == 0 {
+                return stops;
+            }
+            start = block_start(
+                text,
+                start - 1,
+                is_blank(text, start - 1).unwrap_or_default(),
+            );
+        }
+    }
+}
+
+/// Checks if `line` has only whitespace, `None` is returned for lines out of
+/// the buffer.
+fn is_blank(text: &impl LineText, line: usize) -> Option<bool> {
+    text.get_line(line).map(|l| l.trim().is_empty())
+}
+
+/// First line of a block of `blank` (or non-blank) lines with `line`.
+fn block_start(text: &impl LineText, mut line: usize, blank: bool) -> usize {
+    while line > 0 && is_blank(text, line - 1) == Some(blank) {
+        line -= 1;
+    }
+    line
+}
+
+/// Last line of a block of `blank` (or non-blank) lines with `line`.
+fn block_end(text: &impl LineText, mut line: usize, blank: bool) -> usize {
+    while is_blank(text, line + 1) == Some(blank) {
+        line += 1;
+    }
+    line
+}
+
+/// Checks if `ch` is a part of a word.
+pub(crate) fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+/// Class of a character for word motions and objects, a word is a sequence
+/// of characters of the same class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CharClass {
+    Word,
+    Whitespace,
+    Punctuation,
+    Newline,
+}
+
+impl CharClass {
+    pub(crate) fn of(ch: char) -> Self {
+        match ch {
+            '\n' => CharClass::Newline,
+            ch if ch.is_whitespace() => CharClass::Whitespace,
+            ch if is_word_char(ch) => CharClass::Word,
+            _ => CharClass::Punctuation,
+        }
+    }
+}
+
+/// Column ranges of subwords on a line: parts of identifiers split on
+/// underscores, case humps (`parse|Http|Request`, `HTTP|Request`) and digits,
+/// as well as runs of punctuation. Whitespace is not included.
+pub(crate) fn subword_segments(line: &str) -> Vec<Range<usize>> {
+    let chars: Vec<char> = line.chars().collect();
+    let is_hump = |idx: usize| {
+        let (prev, ch) = (chars[idx - 1], chars[idx]);
+        (prev.is_lowercase() && ch.is_uppercase())
+            || (prev.is_uppercase()
+                && ch.is_uppercase()
+                && chars.get(idx + 1).is_some_and(|next| next.is_lowercase()))
+            || prev.is_alphabetic() != ch.is_alphabetic()
+    };
+
+    let mut segments = Vec::new();
+    let mut idx = 0;
+    while idx < chars.len() {
+        let start = idx;
+        idx += 1;
+        match CharClass::of(chars[start]) {
+            CharClass::Word if chars[start] != '_' => {
+                while idx < chars.len()
+                    && is_word_char(chars[idx])
+                    && chars[idx] != '_'
+                    && !is_hump(idx)
+                {
+                    idx += 1;
+                }
+            }
+            CharClass::Punctuation => {
+                while idx < chars.len() && CharClass::of(chars[idx]) == CharClass::Punctuation {
+                    idx += 1;
+                }
+            }
+            _ => continue,
+        }
+        segments.push(start..idx);
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{test_utils::TestText, LineLength};
+
+    #[test]
+    fn offsets_and_positions() {
+        let text = BufferText::new(&TestText::new("ab\nпривет\n\nc"));
+        assert_eq!(text.as_str(), "ab\nпривет\n\nc");
+        for (position, offset) in [
+            (Position::new(0, 1), 1),
+            (Position::new(0, 2), 2),
+            (Position::new(1, 2), 7),
+            (Position::new(1, 6), 15),
+            (Position::new(2, 0), 16),
+            (Position::new(3, 0), 17),
+        ] {
+            assert_eq!(text.offset(&position), offset);
+            assert_eq!(text.position(offset), position);
+        }
+        assert_eq!(text.offset(&Position::new(5, 0)), 18);
+        assert_eq!(text.next_offset(5), 7);
+        assert_eq!(text.last_char_offset(7), 5);
+    }
+
+    #[test]
+    fn lines_range() {
+        let text = BufferText::lines(&TestText::new("ab\nпривет\n\nc"), 1..3);
+        assert_eq!(text.as_str(), "привет\n");
+        assert_eq!(text.offset(&Position::new(1, 2)), 4);
+        assert_eq!(text.position(4), Position::new(1, 2));
+        assert_eq!(text.position(13), Position::new(2, 0));
+        assert_eq!(text.offset(&Position::new(0, 1)), 0);
+        assert_eq!(text.offset(&Position::new(3, 0)), 13);
+    }
+
+    #[test]
+    fn subwords() {
+        assert_eq!(
+            subword_segments("parseHttpRequest(snake_case_name, HTTPServer2go);"),
+            [
+                0..5,
+                5..9,
+                9..16,
+                16..17,
+                17..22,
+                23..27,
+                28..32,
+                32..33,
+                34..38,
+                38..44,
+                44..45,
+                45..47,
+                47..49,
+            ]
+        );
+        assert_eq!(subword_segments("  __init__ x"), [4..8, 11..12]);
+    }
+
+    #[test]
+    fn sentences() {
+        let text = BufferText::new(&TestText::new(
+            "One two. \"Three?\" Four\nfive! v1.2 six\n\n  Seven...\n \nEight",
+        ));
+        assert_eq!(
+            text.sentences(),
+            [0..8, 9..17, 18..28, 29..37, 41..49, 52..57]
+        );
+        assert_eq!(
+            text.sentence_stops(),
+            [
+                Position::new(0, 0),
+                Position::new(0, 9),
+                Position::new(0, 18),
+                Position::new(1, 6),
+                Position::new(2, 0),
+                Position::new(3, 2),
+                Position::new(4, 0),
+                Position::new(5, 0),
+                Position::new(5, 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn sentence_stops_around_cursor() {
+        let text = TestText::new("  One. Two\nthree.\n\n \nFour.\n\nFive. Six.\nSeven");
+        let whole = BufferText::new(&text).sentence_stops();
+        for line in 0..text.lines_count() {
+            for column in 0..=text.get_len(line).unwrap() {
+                let position = Position::new(line, column);
+                for n in 0..5 {
+                    let stops = sentence_stops_around(&text, &position, n, true);
+                    assert_eq!(
+                        position.move_sentence_forward(&stops, n),
+                        position.move_sentence_forward(&whole, n)
+                    );
+                    let stops = sentence_stops_around(&text, &position, n, false);
+                    assert_eq!(
+                        position.move_sentence_backward(&stops, n),
+                        position.move_sentence_backward(&whole, n)
+                    );
+                }
+            }
+        }
+    }
+}
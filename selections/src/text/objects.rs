@@ -0,0 +1,511 @@
+//! Vim-style text objects.
+
+use std::ops::Range;
+
+use super::{block_end, block_start, is_blank, BufferText, CharClass};
+use crate::{LineText, Position, Selection};
+
+/// Lines around the cursor searched for brackets first, the window is doubled
+/// until a pair is found.
+const BRACKETS_WINDOW_RADIUS: usize = 16;
+
+/// Text object to select, see
+/// [SelectionStorage::select_text_object](crate::SelectionStorage::select_text_object).
+/// Each object has an inner and an around version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextObject {
+    /// Sequence of word characters, punctuation or whitespace (`iw`/`aw`),
+    /// around version includes trailing (or leading) whitespace.
+    Word,
+    /// Text between a pair of quote characters on a line (`i"`/`a"`), around
+    /// version includes the quotes. Escaped quotes are skipped.
+    Quote(char),
+    /// Text between an opening and a closing bracket (`i(`/`a(`), around
+    /// version includes the brackets.
+    Brackets(char, char),
+    /// Block of non-blank (or blank) lines (`ip`/`ap`), around version
+    /// includes following (or preceding) blank lines.
+    Paragraph,
+    /// Sentence of prose, possibly spanning several lines (`is`/`as`), ending
+    /// with `.`, `!` or `?` followed by whitespace, or with a blank line.
+    /// Around version includes whitespace up to the next sentence of the
+    /// paragraph (or from the previous one).
+    Sentence,
+}
+
+impl TextObject {
+    /// Finds the object around the cursor of `selection`. Selections longer
+    /// than a single character are extended instead: words, sentences and
+    /// paragraphs following them are added, while brackets already covered are
+    /// replaced with enclosing ones.
+    ///
+    /// Only lines the object may span are read, not the whole buffer.
+    pub(crate) fn find<T>(
+        &self,
+        text: &impl LineText,
+        selection: &Selection<T>,
+        around: bool,
+    ) -> Option<(Position, Position)> {
+        let is_cursor = selection.from.weak_eq(&selection.to);
+        let covered = |(from, to): &(Position, Position)| {
+            !is_cursor && from >= &selection.from && to <= &selection.to
+        };
+
+        match self {
+            TextObject::Word if is_cursor => {
+                let cursor = selection.cursor();
+                let buffer = word_lines(text, cursor.line..cursor.line + 1);
+                let range = word_range(&buffer, buffer.offset(cursor), around)?;
+                Some(buffer.range_positions(&range))
+            }
+            TextObject::Word => {
+                // The next word may begin on the following line:
+                let buffer = word_lines(text, selection.to.line..selection.to.line + 2);
+                let next = buffer.next_offset(buffer.offset(&selection.to));
+                let (_, to) = buffer.range_positions(&word_range(&buffer, next, around)?);
+                Some((selection.from.clone(), to))
+            }
+            TextObject::Quote(quote) => quote_range(text, selection.cursor(), *quote, around),
+            TextObject::Brackets(open, close) => {
+                brackets_range(text, selection.cursor(), *open, *close, around, |found| {
+                    !covered(found)
+                })
+            }
+            TextObject::Paragraph if is_cursor => {
+                paragraph_range(text, selection.cursor().line, around)
+            }
+            TextObject::Paragraph => {
+                let (_, to) = paragraph_range(text, selection.to.line + 1, around)?;
+                Some((selection.from.clone(), to))
+            }
+            TextObject::Sentence if is_cursor => {
+                let cursor = selection.cursor();
+                let buffer =
+                    BufferText::lines(text, sentence_lines(text, cursor.line, cursor.line));
+                let range = sentence_range(&buffer, buffer.offset(cursor), around)?;
+                Some(buffer.range_positions(&range))
+            }
+            TextObject::Sentence => {
+                let to_line = selection.to.line;
+                let buffer = BufferText::lines(text, sentence_lines(text, to_line, to_line + 1));
+                let next = buffer.next_offset(buffer.offset(&selection.to));
+                let (_, to) = buffer.range_positions(&sentence_range(&buffer, next, around)?);
+                Some((selection.from.clone(), to))
+            }
+        }
+    }
+
+    /// Finds the smallest object containing the range from `from` to `to`
+    /// inclusive.
+    pub(crate) fn enclosing(
+        &self,
+        text: &impl LineText,
+        from: &Position,
+        to: &Position,
+        around: bool,
+    ) -> Option<(Position, Position)> {
+        let contains = |(f, t): &(Position, Position)| f <= from && t >= to;
+        match self {
+            TextObject::Word => {
+                let buffer = word_lines(text, from.line..from.line + 1);
+                let range = word_range(&buffer, buffer.offset(from), around)?;
+                Some(buffer.range_positions(&range)).filter(contains)
+            }
+            TextObject::Quote(quote) => quote_range(text, from, *quote, around).filter(contains),
+            TextObject::Brackets(open, close) => {
+                brackets_range(text, from, *open, *close, around, contains)
+            }
+            TextObject::Paragraph => paragraph_range(text, from.line, around).filter(contains),
+            TextObject::Sentence => {
+                let buffer = BufferText::lines(text, sentence_lines(text, from.line, from.line));
+                let range = sentence_range(&buffer, buffer.offset(from), around)?;
+                Some(buffer.range_positions(&range)).filter(contains)
+            }
+        }
+    }
+}
+
+/// Text of `lines` to search words in, the line following them is gathered
+/// too so the last one ends with its newline.
+fn word_lines(text: &impl LineText, lines: Range<usize>) -> BufferText {
+    BufferText::lines(text, lines.start..lines.end + 1)
+}
+
+/// Lines of the first pair of brackets around `cursor` are not known
+/// beforehand, so they are searched in growing windows of lines around it
+/// until a pair is found or the whole buffer is searched.
+fn brackets_range(
+    text: &impl LineText,
+    cursor: &Position,
+    open: char,
+    close: char,
+    around: bool,
+    accept: impl Fn(&(Position, Position)) -> bool,
+) -> Option<(Position, Position)> {
+    let mut radius = BRACKETS_WINDOW_RADIUS;
+    loop {
+        let lines = cursor.line.saturating_sub(radius)..cursor.line + radius + 1;
+        let is_whole = lines.start == 0 && lines.end >= text.lines_count();
+        let buffer = BufferText::lines(text, lines);
+        let found = brackets_range_in(&buffer, cursor, open, close, around, &accept);
+        if found.is_some() || is_whole {
+            return found;
+        }
+        radius *= 2;
+    }
+}
+
+/// Positions of the first pair of brackets around `cursor`, going outwards,
+/// accepted by `accept`.
+fn brackets_range_in(
+    buffer: &BufferText,
+    cursor: &Position,
+    open: char,
+    close: char,
+    around: bool,
+    accept: impl Fn(&(Position, Position)) -> bool,
+) -> Option<(Position, Position)> {
+    let s = buffer.as_str();
+    let cursor = buffer.offset(cursor);
+    let mut open_idx = if s[cursor..].starts_with(open) {
+        cursor
+    } else {
+        find_open(s, cursor, open, close)?
+    };
+    loop {
+        let close_idx = find_close(s, open_idx, open, close)?;
+        let range = if around {
+            open_idx..close_idx + close.len_utf8()
+        } else {
+            open_idx + open.len_utf8()..close_idx
+        };
+        if !range.is_empty() {
+            let found = buffer.range_positions(&range);
+            if accept(&found) {
+                return Some(found);
+            }
+        }
+        open_idx = find_open(s, open_idx, open, close)?;
+    }
+}
+
+/// Byte range of a word on `offset`.
+fn word_range(buffer: &BufferText, offset: usize, around: bool) -> Option<Range<usize>> {
+    let s = buffer.as_str();
+    let class = CharClass::of(s[offset..].chars().next()?);
+    if class == CharClass::Newline {
+        return Some(offset..offset + 1);
+    }
+    let same_class_end = |from: usize, class: CharClass| {
+        s[from..]
+            .char_indices()
+            .find(|(_, ch)| CharClass::of(*ch) != class)
+            .map_or(s.len(), |(idx, _)| from + idx)
+    };
+    let same_class_start = |to: usize, class: CharClass| {
+        s[..to]
+            .char_indices()
+            .rev()
+            .take_while(|(_, ch)| CharClass::of(*ch) == class)
+            .last()
+            .map_or(to, |(idx, _)| idx)
+    };
+
+    let start = same_class_start(offset, class);
+    let end = same_class_end(offset, class);
+    if !around {
+        return Some(start..end);
+    }
+
+    match s[end..].chars().next().map(CharClass::of) {
+        // Whitespace goes with the following word:
+        Some(next) if class == CharClass::Whitespace && next != CharClass::Newline => {
+            Some(start..same_class_end(end, next))
+        }
+        // Word goes with trailing whitespace, or leading one if there is none:
+        Some(CharClass::Whitespace) if class != CharClass::Whitespace => {
+            Some(start..same_class_end(end, CharClass::Whitespace))
+        }
+        _ => Some(same_class_start(start, CharClass::Whitespace)..end),
+    }
+}
+
+/// Positions of quoted text on the cursor line: the pair around the cursor,
+/// or the next one on the line.
+fn quote_range(
+    text: &impl LineText,
+    cursor: &Position,
+    quote: char,
+    around: bool,
+) -> Option<(Position, Position)> {
+    let line = text.get_line(cursor.line)?;
+    let chars: Vec<char> = line.chars().collect();
+    let quotes: Vec<usize> = (0..chars.len())
+        .filter(|idx| chars[*idx] == quote && (*idx == 0 || chars[idx - 1] != '\\'))
+        .collect();
+    let (open, close) = quotes
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .find(|(_, close)| *close >= cursor.column)?;
+
+    let (from, to) = if around {
+        (open, close)
+    } else if close > open + 1 {
+        (open + 1, close - 1)
+    } else {
+        return None;
+    };
+    Some((
+        Position::new(cursor.line, from),
+        Position::new(cursor.line, to),
+    ))
+}
+
+/// Byte offset of the closest unmatched `open` bracket before `end`.
+fn find_open(s: &str, end: usize, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    for (idx, ch) in s[..end].char_indices().rev() {
+        if ch == close {
+            depth += 1;
+        } else if ch == open {
+            if depth == 0 {
+                return Some(idx);
+            }
+            depth -= 1;
+        }
+    }
+    None
+}
+
+/// Byte offset of the bracket closing one on `open_idx`.
+fn find_close(s: &str, open_idx: usize, open: char, close: char) -> Option<usize> {
+    let start = open_idx + open.len_utf8();
+    let mut depth = 0;
+    for (idx, ch) in s[start..].char_indices() {
+        if ch == open {
+            depth += 1;
+        } else if ch == close {
+            if depth == 0 {
+                return Some(start + idx);
+            }
+            depth -= 1;
+        }
+    }
+    None
+}
+
+/// Positions of a paragraph on `line`, the last line is covered with its
+/// newline.
+fn paragraph_range(
+    text: &impl LineText,
+    line: usize,
+    around: bool,
+) -> Option<(Position, Position)> {
+    let blank = is_blank(text, line)?;
+    let mut start = block_start(text, line, blank);
+    let mut end = block_end(text, line, blank);
+    if around {
+        if is_blank(text, end + 1).is_some() {
+            // Following lines of the other kind are included:
+            end = block_end(text, end + 1, !blank);
+        } else if !blank && start > 0 {
+            start = block_start(text, start - 1, true);
+        }
+    }
+
+    Some((
+        Position::new(start, 0),
+        Position::new(end, text.get_len(end)?),
+    ))
+}
+
+/// Lines to search sentences on `first` to `last` lines in: blocks of
+/// non-blank (or blank) lines with them, blank blocks are taken with
+/// neighbouring paragraphs. Sentences don't cross blank lines, so they are
+/// found in these lines just like in the whole buffer.
+fn sentence_lines(text: &impl LineText, first: usize, last: usize) -> Range<usize> {
+    let Some(last_line) = text.lines_count().checked_sub(1) else {
+        return 0..0;
+    };
+    let (first, last) = (first.min(last_line), last.min(last_line));
+
+    let first_blank = is_blank(text, first).unwrap_or_default();
+    let mut start = block_start(text, first, first_blank);
+    if first_blank && start > 0 {
+        start = block_start(text, start - 1, false);
+    }
+    let last_blank = is_blank(text, last).unwrap_or_default();
+    let mut end = block_end(text, last, last_blank);
+    if last_blank && end < last_line {
+        end = block_end(text, end + 1, false);
+    }
+    start..end + 1
+}
+
+/// Byte range of a sentence on `offset`, or of the one following whitespace
+/// on `offset` unless they are separated with a blank line.
+fn sentence_range(buffer: &BufferText, offset: usize, around: bool) -> Option<Range<usize>> {
+    let s = buffer.as_str();
+    let sentences = buffer.sentences();
+    let idx = sentences.partition_point(|sentence| sentence.end <= offset);
+    let sentence = sentences.get(idx)?;
+    // Whitespace between sentences of different paragraphs has a blank line:
+    let same_paragraph = |from: usize, to: usize| s[from..to].matches('\n').count() < 2;
+    let previous_end = idx.checked_sub(1).map(|prev| sentences[prev].end);
+    if offset < sentence.start
+        && s[offset..sentence.start].contains('\n')
+        && !same_paragraph(previous_end.unwrap_or(0), sentence.start)
+    {
+        return None;
+    }
+    if !around {
+        return Some(sentence.clone());
+    }
+
+    // Sentence goes with following whitespace, or leading one if it's the last
+    // one of a paragraph:
+    match (sentences.get(idx + 1), previous_end) {
+        (Some(next), _) if same_paragraph(sentence.end, next.start) => {
+            Some(sentence.start..next.start)
+        }
+        (_, Some(previous_end)) if same_paragraph(previous_end, sentence.start) => {
+            Some(previous_end..sentence.end)
+        }
+        _ => Some(sentence.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::test_utils::TestText;
+
+    fn find(
+        text: &str,
+        object: TextObject,
+        selection: ((usize, usize), (usize, usize)),
+        around: bool,
+    ) -> Option<((usize, usize), (usize, usize))> {
+        let text = TestText::new(text);
+        let selection = Selection::new(
+            Position::new(selection.0 .0, selection.0 .1),
+            Position::new(selection.1 .0, selection.1 .1),
+        );
+        object
+            .find(&text, &selection, around)
+            .map(|(from, to)| ((from.line, from.column), (to.line, to.column)))
+    }
+
+    #[test]
+    fn word() {
+        let text = "let foo_1 = bar;  x\nend";
+        let word = |cursor, around| find(text, TextObject::Word, (cursor, cursor), around);
+        assert_eq!(word((0, 5), false), Some(((0, 4), (0, 8))));
+        assert_eq!(word((0, 5), true), Some(((0, 4), (0, 9))));
+        assert_eq!(word((0, 15), false), Some(((0, 15), (0, 15))));
+        assert_eq!(word((0, 15), true), Some(((0, 15), (0, 17))));
+        assert_eq!(word((0, 16), true), Some(((0, 16), (0, 18))));
+        assert_eq!(word((0, 18), true), Some(((0, 16), (0, 18))));
+        assert_eq!(word((0, 19), false), Some(((0, 19), (0, 19))));
+
+        // Selection is extended with the next word:
+        assert_eq!(
+            find(text, TextObject::Word, ((0, 4), (0, 8)), false),
+            Some(((0, 4), (0, 9)))
+        );
+        assert_eq!(
+            find(text, TextObject::Word, ((0, 4), (0, 9)), true),
+            Some(((0, 4), (0, 11)))
+        );
+    }
+
+    #[test]
+    fn quote() {
+        let text = r#"say("a \"b\"", "", 'c')"#;
+        let quote = |cursor, around| find(text, TextObject::Quote('"'), (cursor, cursor), around);
+        assert_eq!(quote((0, 6), false), Some(((0, 5), (0, 11))));
+        assert_eq!(quote((0, 4), true), Some(((0, 4), (0, 12))));
+        assert_eq!(quote((0, 0), false), Some(((0, 5), (0, 11))));
+        assert_eq!(quote((0, 15), false), None);
+        assert_eq!(quote((0, 15), true), Some(((0, 15), (0, 16))));
+        assert_eq!(quote((0, 20), true), None);
+    }
+
+    #[test]
+    fn brackets() {
+        let text = "f(a, (b\n  + c), ())";
+        let brackets =
+            |selection, around| find(text, TextObject::Brackets('(', ')'), selection, around);
+        assert_eq!(brackets(((1, 2), (1, 2)), false), Some(((0, 6), (1, 4))));
+        assert_eq!(brackets(((0, 5), (0, 5)), true), Some(((0, 5), (1, 5))));
+        assert_eq!(brackets(((1, 5), (1, 5)), false), Some(((0, 6), (1, 4))));
+        assert_eq!(brackets(((0, 2), (0, 2)), true), Some(((0, 1), (1, 10))));
+        assert_eq!(brackets(((0, 0), (0, 0)), false), None);
+
+        // Repeated object is replaced with the enclosing one, empty contents are
+        // skipped:
+        assert_eq!(brackets(((0, 6), (1, 4)), false), Some(((0, 2), (1, 9))));
+        assert_eq!(brackets(((1, 8), (1, 8)), false), Some(((0, 2), (1, 9))));
+    }
+
+    #[test]
+    fn distant_brackets() {
+        let text = format!("{{\n{}x\n{}}}", "(\n".repeat(40), ")\n".repeat(40));
+        let brackets = |selection| find(&text, TextObject::Brackets('{', '}'), selection, true);
+        assert_eq!(brackets(((41, 0), (41, 0))), Some(((0, 0), (82, 0))));
+        assert_eq!(
+            find(
+                &text,
+                TextObject::Brackets('[', ']'),
+                ((41, 0), (41, 0)),
+                true
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn sticky_cursor() {
+        let text = TestText::new("foo bar\nbaz");
+        let cursor = Selection::new(Position::new(0, 5), Position::new_with_sticky(0, 5, 9));
+        assert_eq!(
+            TextObject::Word.find(&text, &cursor, false),
+            Some((Position::new(0, 4), Position::new(0, 6)))
+        );
+    }
+
+    #[test]
+    fn paragraph() {
+        let text = "a\nb\n\n \nc\nd\n\ne";
+        let paragraph = |selection, around| find(text, TextObject::Paragraph, selection, around);
+        assert_eq!(paragraph(((1, 0), (1, 0)), false), Some(((0, 0), (1, 1))));
+        assert_eq!(paragraph(((1, 0), (1, 0)), true), Some(((0, 0), (3, 1))));
+        assert_eq!(paragraph(((2, 0), (2, 0)), false), Some(((2, 0), (3, 1))));
+        assert_eq!(paragraph(((2, 0), (2, 0)), true), Some(((2, 0), (5, 1))));
+        assert_eq!(paragraph(((7, 0), (7, 0)), true), Some(((6, 0), (7, 1))));
+
+        // Selection is extended with the next paragraph:
+        assert_eq!(paragraph(((0, 0), (1, 1)), false), Some(((0, 0), (3, 1))));
+        assert_eq!(paragraph(((0, 0), (3, 1)), true), Some(((0, 0), (6, 0))));
+    }
+
+    #[test]
+    fn sentence() {
+        let text = "One two. Three\nfour!  Five\n\nSix.";
+        let sentence = |selection, around| find(text, TextObject::Sentence, selection, around);
+        assert_eq!(sentence(((0, 1), (0, 1)), false), Some(((0, 0), (0, 7))));
+        assert_eq!(sentence(((0, 1), (0, 1)), true), Some(((0, 0), (0, 8))));
+        assert_eq!(sentence(((1, 1), (1, 1)), false), Some(((0, 9), (1, 4))));
+        assert_eq!(sentence(((0, 8), (0, 8)), false), Some(((0, 9), (1, 4))));
+        assert_eq!(sentence(((1, 8), (1, 8)), true), Some(((1, 5), (1, 10))));
+        assert_eq!(sentence(((1, 11), (1, 11)), false), None);
+        assert_eq!(sentence(((2, 0), (2, 0)), false), None);
+        assert_eq!(sentence(((3, 0), (3, 0)), true), Some(((3, 0), (3, 3))));
+
+        // Selection is extended with the next sentence:
+        assert_eq!(sentence(((0, 0), (0, 7)), false), Some(((0, 0), (1, 4))));
+        assert_eq!(sentence(((0, 0), (1, 6)), true), Some(((0, 0), (1, 10))));
+    }
+}
@@ -35,18 +35,21 @@ impl<T> UniqueSortedVec<T> {
         }
     }
 
-    pub(crate) fn push(&mut self, value: T)
+    /// Puts `value` in its place, returns its index or `None` if an equal
+    /// value is there already.
+    pub(crate) fn push(&mut self, value: T) -> Option<usize>
     where
         T: Ord,
     {
         if self.vec.last().map(|last| last < &value).unwrap_or(true) {
             // If order is maintained just put value in the end
             self.vec.push(value);
+            Some(self.vec.len() - 1)
         } else {
             // If found, do nothing:
-            if let Err(insert_idx) = self.vec.binary_search(&value) {
-                self.vec.insert(insert_idx, value);
-            }
+            let insert_idx = self.vec.binary_search(&value).err()?;
+            self.vec.insert(insert_idx, value);
+            Some(insert_idx)
         }
     }
 
@@ -67,10 +70,10 @@ mod tests {
         vec.push(5);
         vec.push(6);
         vec.push(7);
-        vec.push(1);
+        assert_eq!(vec.push(1), Some(0));
         vec.push(3);
-        vec.push(7);
-        vec.push(4);
+        assert_eq!(vec.push(7), None);
+        assert_eq!(vec.push(4), Some(2));
 
         assert_eq!(vec.as_slice(), &[1, 3, 4, 5, 6, 7]);
     }